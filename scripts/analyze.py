@@ -1,6 +1,7 @@
 import subprocess
 import lola
 import argparse
+import json
 import re
 from graphviz import Source as dot
 import os
@@ -10,6 +11,47 @@ def exec_lola(formula):
     lola.cd_root()
     subprocess.call(["./target/lola/lola-2.0/src/lola", net, formula, "-p"])
 
+def is_reachable(formula):
+    net = "net.lola"
+    lola.cd_root()
+    result = subprocess.run(["./target/lola/lola-2.0/src/lola", net, formula],
+        stdout=subprocess.PIPE, stderr=subprocess.STDOUT, universal_newlines=True)
+    return re.search("result: yes", result.stdout) is not None
+
+def lola_id(node):
+    # the LoLA writer numbers the nodes like the ids of the node map
+    return "t_" + re.search(r"\d+", node).group(0)
+
+def spawned_threads(file):
+    # translate_spawn names the end place of every thread "<closure> end"
+    # (the end places of the entry functions are no closures)
+    threads = []
+    for line in open(file, 'r'):
+        match = re.search(r'\[shape=circle, label="(.*\{\{closure\}\}.*) end', line)
+        if match:
+            threads.append(match.group(1))
+    return threads
+
+def thread_summary(map_file):
+    # every transition that was translated in the body of a thread is checked for EF FIREABLE
+    # a thread where no transition can ever fire never makes progress
+    nodes = json.load(open(map_file, 'r'))
+    lola.cd_root()
+    never_run = []
+    for thread in spawned_threads('./net.dot'):
+        print(thread + ":")
+        reachable = 0
+        for node in [node for node in nodes if node["function"] == thread]:
+            if is_reachable('--formula=EF FIREABLE(' + lola_id(node["node"]) + ')'):
+                print("    " + node["name"] + ": reachable")
+                reachable += 1
+            else:
+                print("    " + node["name"] + ": UNREACHABLE")
+        if reachable == 0:
+            never_run.append(thread)
+    if never_run:
+        print("!!! threads that can never run: " + ", ".join(never_run) + " !!!")
+
 def general_deadlock():
     # p_2 marks program termination
     #exec_lola('--formula=AG(EF(p_2 = 1))')
@@ -64,6 +106,7 @@ if __name__ == "__main__":
     parser.add_argument('-u', '--unconditional-deadlock',action="store_true", help="Search for every deadlock. Even program termination is concidered a deadlock")
     parser.add_argument('-p', '--panic',action="store_true", help="Check if it is possible to reach a panic state")
     parser.add_argument('-n', '--neighbors', nargs="*", help="Generates a subnet with the given nodes and all its neighbors and visualizes it.")
+    parser.add_argument('-t', '--threads', nargs="?", const="net_map.json", help="Reports for every spawned thread which of its transitions can fire. Needs the node map (written by --map, default net_map.json). Threads where no transition can fire are flagged.")
     parser.add_argument('-v', '--visualize', action="store_true", help="visualize the graph in graphviz. Doesn't terminate (in time) for larger graphs")
 
     args = parser.parse_args()
@@ -76,5 +119,7 @@ if __name__ == "__main__":
         can_panic()
     if args.neighbors:
         neighbors(args.neighbors)
+    if args.threads:
+        thread_summary(args.threads)
     if args.visualize:
         visualize("net.dot")
\ No newline at end of file
@@ -11,6 +11,11 @@ def run(filename):
         os.getcwd() + "/tests/sample_programs/" + filename,
         "--",
         "--mir_dump",  
+        "--output",
+        "net",
+        "--force",
+        "--map",
+        "net_map.json",
         "--format", 
         "pnml", 
         "lola", 
@@ -0,0 +1,73 @@
+// DOT export of the net for graphviz.
+// The nodes and arcs are taken from the record of the net (`Net`) like for the JSON export.
+// Places are circles and transitions boxes, the nodes of every function are drawn in a
+// cluster of their own and a marked place shows its tokens.
+
+use crate::petri_net::net::{node_id, Net, Node, NodeKind};
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+pub fn write<W: Write>(net: &Net, mut writer: W) -> Result<()> {
+    // the nodes of the program and of shared resources belong to no function
+    let mut functions: BTreeMap<&str, Vec<&Node>> = BTreeMap::new();
+    let mut globals = Vec::new();
+    for node in net.nodes() {
+        match &node.function {
+            Some(function) => functions.entry(function.as_str()).or_default().push(node),
+            None => globals.push(node),
+        }
+    }
+
+    writeln!(writer, "digraph net {{")?;
+    for node in globals {
+        write_node(&mut writer, node, "    ")?;
+    }
+    for (index, (function, nodes)) in functions.iter().enumerate() {
+        writeln!(writer, "    subgraph cluster_{} {{", index)?;
+        writeln!(writer, "        label=\"{}\";", escape(function))?;
+        for node in nodes {
+            write_node(&mut writer, node, "        ")?;
+        }
+        writeln!(writer, "    }}")?;
+    }
+    for arc in net.arcs() {
+        writeln!(
+            writer,
+            "    \"{}\" -> \"{}\";",
+            escape(&node_id(arc.source)),
+            escape(&node_id(arc.target))
+        )?;
+    }
+    writeln!(writer, "}}")
+}
+
+fn write_node<W: Write>(writer: &mut W, node: &Node, indent: &str) -> Result<()> {
+    let id = escape(&node_id(node.node));
+    let name = match &node.name {
+        Some(name) => escape(name),
+        None => id.clone(),
+    };
+    match node.kind {
+        NodeKind::Place => {
+            let label = match node.marking {
+                0 => name,
+                1 => format!("{}\\n\u{2022}", name),
+                tokens => format!("{}\\n{} \u{2022}", name, tokens),
+            };
+            writeln!(
+                writer,
+                "{}\"{}\" [shape=circle, label=\"{}\"];",
+                indent, id, label
+            )
+        }
+        NodeKind::Transition => writeln!(
+            writer,
+            "{}\"{}\" [shape=box, label=\"{}\"];",
+            indent, id, name
+        ),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
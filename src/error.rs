@@ -0,0 +1,68 @@
+// The errors of the translation.
+// Errors of the net library are wrapped, everything else describes the MIR that
+// could not be translated.
+
+use rustc_hir::def_id::DefId;
+use std::fmt;
+
+// the error type of the net library (it is only exposed through its `Result`)
+trait ResultError {
+    type Error;
+}
+
+impl<T, E> ResultError for std::result::Result<T, E> {
+    type Error = E;
+}
+
+pub type NetError = <petri_to_star::Result<()> as ResultError>::Error;
+
+pub type Result<T> = std::result::Result<T, TranslationError>;
+
+#[derive(Debug)]
+pub enum TranslationError {
+    /// a frame was expected on the call stack
+    EmptyCallStack,
+    /// the net rejected a node or an arc
+    CorruptNetReference(NetError),
+    /// a terminator or statement that cannot be modeled (only reported in strict mode)
+    UnsupportedTerminator(String),
+    /// a function that has to be translated has no MIR body
+    MirUnavailable(DefId),
+    /// the MIR does not have the expected shape
+    UnexpectedMir(String),
+    /// the summary file cannot be loaded
+    Summary(String),
+    /// the crate has no main function (e.g. a library)
+    NoEntryFunction,
+    /// a construct that violates the requested 1-safety of the net
+    NotOneSafe(String),
+    /// the `--dump-mir` file cannot be written
+    MirDump(String),
+    /// the size of the net cannot be serialized (`--stats-json`)
+    Stats(String),
+}
+
+impl fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslationError::EmptyCallStack => write!(f, "empty call stack"),
+            TranslationError::CorruptNetReference(err) => write!(f, "net error: {:?}", err),
+            TranslationError::UnsupportedTerminator(kind) => write!(f, "unsupported {}", kind),
+            TranslationError::MirUnavailable(def_id) => write!(f, "no mir for {:?}", def_id),
+            TranslationError::UnexpectedMir(msg) => write!(f, "unexpected mir: {}", msg),
+            TranslationError::Summary(msg) => write!(f, "summary error: {}", msg),
+            TranslationError::NoEntryFunction => write!(f, "no entry function"),
+            TranslationError::NotOneSafe(construct) => write!(f, "not 1-safe: {}", construct),
+            TranslationError::MirDump(err) => write!(f, "unable to dump mir: {}", err),
+            TranslationError::Stats(err) => write!(f, "unable to serialize the net size: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TranslationError {}
+
+impl From<NetError> for TranslationError {
+    fn from(err: NetError) -> Self {
+        TranslationError::CorruptNetReference(err)
+    }
+}
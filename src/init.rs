@@ -1,19 +1,65 @@
 // inspired by and based on miri: https://github.com/rust-lang/miri/blob/master/src/bin/miri.rs
 
+use clap::ArgMatches;
+use granite::translator::Granularity;
+use granite::Options;
 use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
 use std::str::FromStr;
+use std::sync::Mutex;
 
-pub fn init_early_loggers() {
-    env_logger::init();
+pub fn init_early_loggers(arguments: &FairumArgs) {
+    let mut builder = env_logger::Builder::new();
+    // stdout is kept free for the output of the translation
+    builder.target(env_logger::Target::Stderr);
+    // `--log-level` overrides the filter of `RUST_LOG`
+    match (&arguments.log_level, env::var("RUST_LOG")) {
+        (Some(level), _) => {
+            builder.parse_filters(level);
+        }
+        (None, Ok(filter)) => {
+            builder.parse_filters(&filter);
+        }
+        (None, Err(_)) => {}
+    }
+    if let Some(path) = &arguments.log_file {
+        let file = std::fs::File::create(path).unwrap_or_else(|err| {
+            eprintln!(
+                "error: unable to create log file {}: {}",
+                path.display(),
+                err
+            );
+            std::process::exit(2)
+        });
+        let file = Mutex::new(file);
+        // the log is tee'd: to stderr as usual and into the file
+        builder.format(move |buf, record| {
+            let line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+            if let Ok(mut file) = file.lock() {
+                // a failing log file must not abort the translation
+                let _ = writeln!(file, "{}", line);
+            }
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.init();
     if env::var("RUSTC_LOG").is_ok() {
         rustc_driver::init_rustc_env_logger();
     }
 }
 
-pub fn init_late_loggers() {
+pub fn init_late_loggers(arguments: &FairumArgs) {
     // We initialize loggers right before we start evaluation. We overwrite the `RUSTC_LOG`
-    // env var if it is not set, control it based on `RUST_LOG`.
-    if let Ok(var) = env::var("RUST_LOG") {
+    // env var if it is not set, control it based on `RUST_LOG` (or `--log-level`).
+    // The logger of rustc is separate from ours and always writes to stderr,
+    // so only our own log ends up in `--log-file`.
+    let var = match &arguments.log_level {
+        Some(level) => Ok(level.clone()),
+        None => env::var("RUST_LOG"),
+    };
+    if let Ok(var) = var {
         if env::var("RUSTC_LOG").is_err() {
             if log::Level::from_str(&var).is_ok() {
                 env::set_var(
@@ -30,11 +76,13 @@ pub fn init_late_loggers() {
 
 /// Returns the "default sysroot" that we will use if no `--sysroot` flag is set.
 /// Should be a compile-time constant.
-fn compile_time_sysroot() -> Option<String> {
+/// `Ok(None)` means librustc can compute the sysroot itself,
+/// `Err` that the build environment did not tell us a sysroot.
+fn compile_time_sysroot() -> Result<Option<String>, ()> {
     if option_env!("RUSTC_STAGE").is_some() {
         // This is being built as part of rustc, and gets shipped with rustup.
         // We can rely on the sysroot computation in librustc.
-        return None;
+        return Ok(None);
     }
     // For builds outside rustc, we need to ensure that we got a sysroot
     // that gets used as a default.  The sysroot computation in librustc would
@@ -42,12 +90,36 @@ fn compile_time_sysroot() -> Option<String> {
     // Taken from PR <https://github.com/Manishearth/rust-clippy/pull/911>.
     let home = option_env!("RUSTUP_HOME").or(option_env!("MULTIRUST_HOME"));
     let toolchain = option_env!("RUSTUP_TOOLCHAIN").or(option_env!("MULTIRUST_TOOLCHAIN"));
-    Some(match (home, toolchain) {
-        (Some(home), Some(toolchain)) => format!("{}/toolchains/{}", home, toolchain),
+    match (home, toolchain) {
+        (Some(home), Some(toolchain)) => Ok(Some(format!("{}/toolchains/{}", home, toolchain))),
         _ => option_env!("RUST_SYSROOT")
-            .expect("To build without rustup, set the `RUST_SYSROOT` env var at build time")
-            .to_owned(),
-    })
+            .map(|sysroot| Some(sysroot.to_owned()))
+            .ok_or(()),
+    }
+}
+
+/// Asks rustup (with the toolchain of the `rust-toolchain` file) and then
+/// the rustc in the path for their sysroot.
+fn queried_sysroot() -> Option<String> {
+    let toolchain = option_env!("RUSTUP_TOOLCHAIN").unwrap_or_else(|| {
+        include_str!("../rust-toolchain")
+            .lines()
+            .next()
+            .unwrap_or("nightly")
+            .trim()
+    });
+    print_sysroot(Command::new("rustup").args(&["run", toolchain, "rustc"]))
+        .or_else(|| print_sysroot(&mut Command::new("rustc")))
+}
+
+fn print_sysroot(rustc: &mut Command) -> Option<String> {
+    let output = rustc.args(&["--print", "sysroot"]).output().ok()?;
+    let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if output.status.success() && !sysroot.is_empty() {
+        Some(sysroot)
+    } else {
+        None
+    }
 }
 
 pub fn parse_arguments() -> (Vec<String>, Vec<String>) {
@@ -79,12 +151,196 @@ pub fn parse_arguments() -> (Vec<String>, Vec<String>) {
 pub fn check_sysroot(rustc_args: &mut Vec<String>) {
     // Determine sysroot if needed.  Make sure we always call `compile_time_sysroot`
     // as that also does some sanity-checks of the environment we were built in.
-    if let Some(sysroot) = compile_time_sysroot() {
-        let sysroot_flag = "--sysroot";
-        if !rustc_args.iter().any(|e| e == sysroot_flag) {
-            // We need to overwrite the default that librustc would compute.
-            rustc_args.push(sysroot_flag.to_owned());
-            rustc_args.push(sysroot);
+    let compile_time = compile_time_sysroot();
+    let sysroot_flag = "--sysroot";
+    if rustc_args.iter().any(|e| e == sysroot_flag) {
+        return;
+    }
+    let sysroot = match compile_time {
+        Ok(Some(sysroot)) => sysroot,
+        Ok(None) => return,
+        Err(()) => queried_sysroot().unwrap_or_else(|| {
+            eprintln!(
+                "error: unable to find a sysroot; install the toolchain of the `rust-toolchain` \
+                 file with rustup, pass `--sysroot` or set `RUST_SYSROOT` at build time"
+            );
+            std::process::exit(2)
+        }),
+    };
+    // We need to overwrite the default that librustc would compute.
+    rustc_args.push(sysroot_flag.to_owned());
+    rustc_args.push(sysroot);
+}
+
+/// The formats a net can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pnml,
+    Lola,
+    Dot,
+    Json,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Pnml => "pnml",
+            Format::Lola => "lola",
+            Format::Dot => "dot",
+            Format::Json => "json",
         }
     }
 }
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "pnml" => Ok(Format::Pnml),
+            "lola" => Ok(Format::Lola),
+            "dot" => Ok(Format::Dot),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {}", format)),
+        }
+    }
+}
+
+/// The arguments after `--` (everything before is passed to rustc)
+#[derive(Debug)]
+pub struct FairumArgs {
+    pub options: Options,
+    pub emit: Vec<Format>,
+    pub output: Option<PathBuf>,
+    pub force: bool,
+    pub map: Option<PathBuf>,
+    pub mir_dump: Option<PathBuf>,
+    pub entry: Vec<String>,
+    pub all_functions: bool,
+    pub output_dir: PathBuf,
+    pub server: bool,
+    pub log_file: Option<PathBuf>,
+    pub log_level: Option<String>,
+    pub quiet: bool,
+}
+
+impl FairumArgs {
+    /// Validates the matches of the command line of `main`
+    pub fn from_matches(arguments: &ArgMatches<'_>) -> Result<Self, String> {
+        let emit = match arguments.values_of("output_format") {
+            Some(formats) => formats
+                .map(str::parse)
+                .collect::<Result<Vec<Format>, String>>()?,
+            None => vec![Format::Pnml],
+        };
+        // `--simplify` is only an alias of `--granularity sync`
+        // (the granularity has a default value, so clap cannot check the conflict)
+        if arguments.is_present("simplify")
+            && (arguments.is_present("block_transitions")
+                || arguments.occurrences_of("granularity") > 0)
+        {
+            return Err(
+                "--simplify cannot be combined with --granularity or --block-transitions".into(),
+            );
+        }
+        let options = Options {
+            watchdog: number(arguments, "watchdog")?.map(std::time::Duration::from_secs),
+            granularity: if arguments.is_present("block_transitions") {
+                Granularity::Block
+            } else if arguments.is_present("simplify") {
+                Granularity::Sync
+            } else {
+                match arguments.value_of("granularity") {
+                    Some("block") => Granularity::Block,
+                    Some("sync") => Granularity::Sync,
+                    _ => Granularity::Statement,
+                }
+            },
+            prune_panics: arguments.is_present("prune_panics"),
+            model_overflow: arguments.is_present("model_overflow"),
+            fn_pointer_candidates: arguments.is_present("fn_pointer_candidates"),
+            annotate_source: arguments.is_present("annotate_source"),
+            strict: arguments.is_present("strict"),
+            resolve_dyn: arguments.is_present("resolve_dyn"),
+            atomic: atomic_functions(arguments)?,
+            exclude: match arguments.values_of("exclude") {
+                Some(modules) => modules.map(String::from).collect(),
+                None => Vec::new(),
+            },
+            force_inline: match arguments.values_of("force_inline") {
+                Some(paths) => paths.map(String::from).collect(),
+                None => Vec::new(),
+            },
+            summaries: arguments.value_of("summaries").map(PathBuf::from),
+            // the json export takes the spans of the transitions from the node map
+            node_map: arguments.is_present("map") || emit.contains(&Format::Json),
+            one_safe: arguments.is_present("one_safe"),
+            unreachable_sink: arguments.is_present("unreachable_sink"),
+            rwlock_readers: number(arguments, "rwlock_readers")?.map(|readers| readers as usize),
+            max_depth: number(arguments, "max_depth")?.map(|depth| depth as usize),
+            stats_json: arguments.is_present("stats_json"),
+            quiet: arguments.is_present("quiet"),
+            debug_functions: arguments.is_present("debug_functions"),
+        };
+        Ok(FairumArgs {
+            options,
+            emit,
+            output: arguments.value_of("output").map(PathBuf::from),
+            force: arguments.is_present("force"),
+            map: arguments.value_of("map").map(PathBuf::from),
+            mir_dump: if arguments.is_present("mir_dump") {
+                Some(PathBuf::from(
+                    arguments.value_of("mir_dump").unwrap_or("net.mir"),
+                ))
+            } else {
+                None
+            },
+            entry: match arguments.values_of("entry") {
+                Some(entries) => entries.map(String::from).collect(),
+                None => Vec::new(),
+            },
+            all_functions: arguments.is_present("all_functions"),
+            output_dir: PathBuf::from(arguments.value_of("output_dir").unwrap_or(".")),
+            server: arguments.is_present("server"),
+            log_file: arguments.value_of("log_file").map(PathBuf::from),
+            // only errors are logged in quiet mode
+            log_level: if arguments.is_present("quiet") {
+                Some("error".to_owned())
+            } else {
+                arguments.value_of("log_level").map(String::from)
+            },
+            quiet: arguments.is_present("quiet"),
+        })
+    }
+}
+
+fn number(arguments: &ArgMatches<'_>, name: &str) -> Result<Option<u64>, String> {
+    match arguments.value_of(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| {
+            format!(
+                "--{} has to be a number, not {}",
+                name.replace('_', "-"),
+                value
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+fn atomic_functions(arguments: &ArgMatches<'_>) -> Result<Vec<String>, String> {
+    let mut atomic: Vec<String> = match arguments.values_of("treat_atomic") {
+        Some(paths) => paths.map(String::from).collect(),
+        None => Vec::new(),
+    };
+    if let Some(file) = arguments.value_of("atomic_list") {
+        let list = std::fs::read_to_string(file)
+            .map_err(|err| format!("unable to read atomic list {}: {}", file, err))?;
+        atomic.extend(
+            list.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+    Ok(atomic)
+}
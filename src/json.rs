@@ -0,0 +1,102 @@
+// JSON export of the net for tools that cannot read PNML.
+// The nodes and arcs are taken from the record of the net (`Net`), so the export describes
+// exactly the net that is written in the other formats. The ids of the nodes are the ids
+// in the node map (`NodeSource::node`).
+
+use crate::petri_net::function::NodeSource;
+use crate::petri_net::net::{node_id, Net, NodeKind};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Incremented on every incompatible change of the schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct JsonNet {
+    pub version: u32,
+    /// the id of the place that is marked in the initial marking
+    pub start: Option<String>,
+    /// the id of the place that is marked when the program returns normally
+    pub end: Option<String>,
+    pub places: Vec<JsonPlace>,
+    pub transitions: Vec<JsonTransition>,
+    pub arcs: Vec<JsonArc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonPlace {
+    pub id: String,
+    pub name: String,
+    /// the number of tokens in the initial marking
+    pub marking: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonTransition {
+    pub id: String,
+    pub name: String,
+    /// the MIR span the transition was translated from (only known with a node map)
+    pub span: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonArc {
+    pub source: String,
+    pub target: String,
+    pub weight: usize,
+    #[serde(rename = "type")]
+    pub kind: ArcKind,
+}
+
+/// The net library only has plain arcs, the field leaves room for other kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArcKind {
+    Normal,
+}
+
+impl JsonNet {
+    /// The spans of the transitions are looked up in `nodes` by the id of the transition.
+    pub fn read(net: &Net, nodes: &[NodeSource]) -> Self {
+        let spans: HashMap<&str, &Option<String>> = nodes
+            .iter()
+            .map(|node| (node.node.as_str(), &node.span))
+            .collect();
+        let mut json = JsonNet {
+            version: SCHEMA_VERSION,
+            start: net.start_place().map(node_id),
+            end: net.end_place().map(node_id),
+            ..JsonNet::default()
+        };
+        for node in net.nodes() {
+            let id = node_id(node.node);
+            let name = node.name.clone().unwrap_or_else(|| id.clone());
+            match node.kind {
+                NodeKind::Place => json.places.push(JsonPlace {
+                    id,
+                    name,
+                    marking: node.marking,
+                }),
+                NodeKind::Transition => json.transitions.push(JsonTransition {
+                    span: spans.get(id.as_str()).and_then(|span| (*span).clone()),
+                    id,
+                    name,
+                }),
+            }
+        }
+        for arc in net.arcs() {
+            json.arcs.push(JsonArc {
+                source: node_id(arc.source),
+                target: node_id(arc.target),
+                weight: 1,
+                kind: ArcKind::Normal,
+            });
+        }
+        json
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
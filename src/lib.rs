@@ -0,0 +1,51 @@
+//! Translation of rust programs into petri nets.
+//! The translation runs inside a rustc driver and works on the optimized MIR of a crate.
+//! The `granite` binary is one such driver; other drivers can call `translate_crate`
+//! (or use a `Translator` directly) in their `after_analysis` callback.
+
+#![feature(rustc_private)]
+#![deny(rust_2018_idioms)]
+#![feature(option_expect_none)]
+#![feature(box_patterns)]
+
+#[macro_use]
+extern crate log;
+
+#[macro_use]
+extern crate rustc;
+extern crate rustc_hir;
+extern crate rustc_index;
+extern crate rustc_mir;
+
+pub mod dot;
+mod error;
+pub mod json;
+mod petri_net;
+pub mod server;
+pub mod stats;
+pub mod summary;
+pub mod translator;
+mod watchdog;
+
+pub use crate::error::{Result, TranslationError};
+pub use crate::json::JsonNet;
+pub use crate::petri_net::function::NodeSource;
+pub use crate::petri_net::net::Net;
+pub use crate::stats::{FunctionStats, NetStats};
+pub use crate::translator::{Options, Translator};
+pub use petri_to_star::PetriNet;
+
+use rustc::ty::TyCtxt;
+use rustc_hir::def_id::LOCAL_CRATE;
+
+/// Translates the program that starts in the main function of the local crate.
+/// Fails with `TranslationError::NoEntryFunction` if the crate has no main function.
+pub fn translate_crate(tcx: TyCtxt<'_>, options: Options) -> Result<PetriNet> {
+    let (entry_def_id, _) = tcx
+        .entry_fn(LOCAL_CRATE)
+        .ok_or(TranslationError::NoEntryFunction)?;
+    let mut translator =
+        Translator::new(tcx, tcx.crate_name(LOCAL_CRATE).to_string(), None, options)?;
+    translator.petrify(entry_def_id)?;
+    Ok(translator.into_net())
+}
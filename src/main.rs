@@ -1,70 +1,138 @@
 #![feature(rustc_private)]
 #![deny(rust_2018_idioms)]
-#![feature(option_expect_none)]
-#![feature(box_patterns)]
 
 #[macro_use]
 extern crate log;
 
-#[macro_use]
 extern crate rustc;
 extern crate rustc_driver;
 extern crate rustc_hir;
-extern crate rustc_index;
 extern crate rustc_interface;
-extern crate rustc_mir;
 
 mod init;
-mod petri_net;
-mod translator;
 
-use crate::translator::Translator;
-use clap::{Arg, ArgMatches};
+use clap::Arg;
+use granite::{dot, server, JsonNet, Net, NodeSource, Options, Translator};
+use init::{FairumArgs, Format};
+use rustc::ty::TyCtxt;
 use rustc_driver::Compilation;
 use rustc_hir::def_id::LOCAL_CRATE;
 use rustc_interface::interface;
 use rustc_interface::Queries;
+use std::io::Write;
+use std::path::Path;
 
-struct PetriConfig<'a> {
-    arguments: ArgMatches<'a>,
+struct PetriConfig {
+    arguments: FairumArgs,
 }
 
-impl<'a> rustc_driver::Callbacks for PetriConfig<'a> {
+impl rustc_driver::Callbacks for PetriConfig {
     fn after_analysis<'tcx>(
         &mut self,
         compiler: &interface::Compiler,
         queries: &'tcx Queries<'tcx>,
     ) -> Compilation {
-        init::init_late_loggers();
+        init::init_late_loggers(&self.arguments);
         compiler.session().abort_if_errors();
 
         queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
-            let (entry_def_id, _) = tcx.entry_fn(LOCAL_CRATE).expect("no main function found!");
-            let mir_dump = match self.arguments.values_of("mir_dump") {
-                Some(path) => Some(out_file("mir")),
+            let arguments = &self.arguments;
+            let options = arguments.options.clone();
+            if arguments.server {
+                info!("serving requests on stdin");
+                server::serve(tcx, &options);
+                return;
+            }
+            let force = arguments.force;
+            if arguments.all_functions {
+                translate_all_functions(
+                    tcx,
+                    &options,
+                    &arguments.emit,
+                    &arguments.output_dir,
+                    force,
+                );
+                return;
+            }
+            let entries: Vec<_> = if arguments.entry.is_empty() {
+                match tcx.entry_fn(LOCAL_CRATE) {
+                    Some((def_id, _)) => vec![def_id],
+                    None => {
+                        error!("no entry function; pass --entry or --all-functions");
+                        exit_with_candidates(tcx)
+                    }
+                }
+            } else {
+                arguments
+                    .entry
+                    .iter()
+                    .map(|path| {
+                        server::find_function(tcx, path).unwrap_or_else(|| {
+                            error!("function {} not found", path);
+                            exit_with_candidates(tcx)
+                        })
+                    })
+                    .collect()
+            };
+            let mir_dump: Option<Box<dyn std::io::Write>> = match &arguments.mir_dump {
+                Some(path) if path == Path::new("-") => Some(Box::new(std::io::stderr())),
+                Some(path) => Some(Box::new(out_file(path, force))),
                 None => None,
             };
-            let mut pass = Translator::new(tcx, mir_dump).expect("Unable to create translator");
-            let net = pass.petrify(entry_def_id).expect("translation failed");
-            for format in self
-                .arguments
-                .values_of("output_format")
-                .expect("no output format given")
-            {
-                let mut file = out_file(format);
-                if format == "pnml" {
-                    info!("generating pnml");
-                    net.to_pnml(&mut file).expect("write error");
-                }
-                if format == "lola" {
-                    info!("generating lola");
-                    net.to_lola(&mut file).expect("write error");
+            let mut pass = Translator::new(
+                tcx,
+                tcx.crate_name(LOCAL_CRATE).to_string(),
+                mir_dump,
+                options,
+            )
+            .unwrap_or_else(|err| {
+                error!("unable to create the translator: {}", err);
+                std::process::exit(1)
+            });
+            if let Err(err) = pass.petrify_entries(&entries) {
+                error!("translation failed: {}", err);
+                std::process::exit(1)
+            }
+            let net = pass.net();
+            let to_stdout = match &arguments.output {
+                None => true,
+                Some(output) => output == Path::new("-"),
+            };
+            if to_stdout && arguments.emit.len() > 1 {
+                error!("multiple formats cannot be written to stdout, pass --output");
+                std::process::exit(1)
+            }
+            for format in &arguments.emit {
+                match &arguments.output {
+                    Some(output) if !to_stdout => {
+                        // one file per format next to each other
+                        let path = if arguments.emit.len() == 1 {
+                            output.clone()
+                        } else {
+                            output.with_extension(format.extension())
+                        };
+                        write_file(net, *format, pass.node_map(), &path, force)
+                    }
+                    // only the net is written to stdout, everything else goes to stderr
+                    _ => write_stdout(net, *format, pass.node_map()),
                 }
-                if format == "dot" {
-                    info!("generating dot");
-                    net.to_dot(&mut file).expect("write error");
+            }
+            if let Some(map) = &arguments.map {
+                let mut file = out_file(map, force);
+                let written = serde_json::to_writer_pretty(&mut file, pass.node_map())
+                    .map_err(|err| err.to_string())
+                    .and_then(|_| file.sync_all().map_err(|err| err.to_string()));
+                if let Err(err) = written {
+                    error!("unable to write the node map: {}", err);
+                    std::process::exit(1)
                 }
             }
+            // the initial and the final marking for reachability queries
+            // (on stderr, stdout only carries the net)
+            if !arguments.quiet {
+                eprintln!("start place: {}", pass.start_place_name());
+                eprintln!("end place: {}", pass.end_place_name());
+            }
         });
 
         compiler.session().abort_if_errors();
@@ -73,7 +141,6 @@ impl<'a> rustc_driver::Callbacks for PetriConfig<'a> {
     }
 }
 pub fn main() {
-    init::init_early_loggers();
     let matches = clap::App::new("granite")
         .version("0.1")
         .author("Tom Meyer <tom.meyer89@gmail.com>")
@@ -81,16 +148,250 @@ pub fn main() {
         .arg(
             Arg::with_name("output_format")
                 .long("format")
+                .alias("emit")
                 .value_name("FORMAT")
                 .help("Defines the output standard for the generated petri net")
-                .possible_values(&["pnml", "lola", "dot"])
+                .possible_values(&["pnml", "lola", "dot", "json"])
                 .multiple(true)
                 .default_value("pnml"),
         )
         .arg(
             Arg::with_name("mir_dump")
-                .long("mir_dump")
-                .help("Dumps pretty printed mir into the given file")
+                .long("dump-mir")
+                .alias("mir_dump")
+                .value_name("PATH")
+                .help("Dumps the mir of every translated function in translation order into the given file (default net.mir, - for stderr)")
+                .takes_value(true)
+                .min_values(0)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("watchdog")
+                .long("watchdog")
+                .value_name("SECONDS")
+                .help("Aborts the translation if it makes no progress for the given time")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("block_transitions")
+                .long("block-transitions")
+                .help("Folds the statements of a basic block that have no side effects into one transition (same as --granularity block)")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("granularity")
+                .long("granularity")
+                .value_name("GRANULARITY")
+                .help("Models every statement, every block with folded statements (control flow skeleton) or only branches, calls and synchronization (sync) as transitions")
+                .possible_values(&["statement", "block", "sync"])
+                .default_value("statement"),
+        )
+        .arg(
+            Arg::with_name("simplify")
+                .long("simplify")
+                .help("Alias of --granularity sync, chains of silent transitions are not generated (conflicts with --granularity and --block-transitions)")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("prune_panics")
+                .long("prune-panics")
+                .help("Omits the failing paths of assertions from the net")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("model_overflow")
+                .long("model-overflow")
+                .help("Keeps the panics of arithmetic overflow checks if panics are pruned")
+                .requires("prune_panics")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("fn_pointer_candidates")
+                .long("fn-pointer-candidates")
+                .help("Calls every function whose address was taken if a function pointer cannot be resolved (can explode the net)")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("treat_atomic")
+                .long("treat-atomic")
+                .value_name("PATH")
+                .help("Models the function (or all functions of the module) with the given path as a single transition")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("atomic_list")
+                .long("atomic-list")
+                .value_name("FILE")
+                .help("Like --treat-atomic for every line of the file (lines starting with # are ignored)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("MODULE")
+                .help("Does not translate the functions of the module (e.g. log::) but models their calls as single transitions")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("force_inline")
+                .long("force-inline")
+                .value_name("PATH")
+                .help("Translates the function (or all functions of the module) even if it matches --treat-atomic, --exclude or is deeper than --max-depth")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("summaries")
+                .long("summaries")
+                .value_name("FILE")
+                .help("Models the functions of the TOML file by their summary (consumed and produced resources)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("one_safe")
+                .long("one-safe")
+                .help("Aborts on constructs that need more than one token on a control flow place (recursion, threads spawned in loops)")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("unreachable_sink")
+                .long("unreachable-sink")
+                .help("Models unreachable code as a transition into an \"unreachable\" place instead of a dead end")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("rwlock_readers")
+                .long("rwlock-readers")
+                .value_name("N")
+                .help("The number of readers that can hold a RwLock at the same time (default 4)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("max_depth")
+                .long("max-depth")
+                .value_name("N")
+                .help("Models calls deeper than N (main has depth 1) as single transitions")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("resolve_dyn")
+                .long("resolve-dyn")
+                .help("Calls every implementation of the crate for a call through a trait object (can explode the net)")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Aborts the translation on constructs that cannot be modeled")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("annotate_source")
+                .long("annotate-source")
+                .help("Appends the source location to every transition name (bloats the output)")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("debug_functions")
+                .long("debug-functions")
+                .help("Prints the places of the blocks and locals of every translated function to stderr")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("stats_json")
+                .long("stats-json")
+                .help("Prints the size of the net (places, transitions, arcs, functions) as JSON to stderr")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .value_name("PATH")
+                .help("Writes the net to the given file instead of stdout (- for stdout, the extension is replaced by the format if multiple formats are given)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("map")
+                .long("map")
+                .value_name("PATH")
+                .help("Writes a JSON file that maps every transition to its function, block and source location")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Overwrites existing output files")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("all_functions")
+                .long("all-functions")
+                .help("Translates every function of the crate into its own net (e.g. for libraries)")
+                .conflicts_with_all(&["entry", "output"])
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("output_dir")
+                .long("output-dir")
+                .value_name("DIR")
+                .help("The directory of the nets of --all-functions")
+                .takes_value(true)
+                .requires("all_functions")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("entry")
+                .long("entry")
+                .value_name("PATH")
+                .help("Translates the given function (e.g. path::to::function) instead of main, multiple entries run as concurrent tasks in one net")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("log_file")
+                .long("log-file")
+                .value_name("PATH")
+                .help("Writes the log into the given file in addition to stderr (without the log of rustc)")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("log_level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .help("Overrides the log filter of RUST_LOG")
+                .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .help("Logs only errors and prints neither the net size (unless --stats-json is given) nor the start and end place")
+                .conflicts_with("log_level")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("server")
+                .long("server")
+                .help("Keeps running and answers line delimited JSON requests on stdin")
                 .required(false),
         );
     let (mut rustc_args, mut granite_args) = init::parse_arguments();
@@ -98,9 +399,15 @@ pub fn main() {
 
     // clap needs an executable path (or at least the first argument is ignored in parsing)
     granite_args.insert(0, rustc_args.first().unwrap().into());
-    let mut config = PetriConfig {
-        arguments: matches.get_matches_from(granite_args),
+    let arguments = match FairumArgs::from_matches(&matches.get_matches_from(granite_args)) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
     };
+    init::init_early_loggers(&arguments);
+    let mut config = PetriConfig { arguments };
     let result = rustc_driver::catch_fatal_errors(move || {
         rustc_driver::run_compiler(&rustc_args, &mut config, None, None)
     })
@@ -108,11 +415,128 @@ pub fn main() {
     std::process::exit(result.is_err() as i32);
 }
 
-fn out_file(format: &str) -> std::fs::File {
-    match std::fs::File::create(format!("net.{}", format)) {
+// Translates every function of the crate into its own net,
+// e.g. `module::function` is written to `module.function.pnml`
+fn translate_all_functions(
+    tcx: TyCtxt<'_>,
+    options: &Options,
+    formats: &[Format],
+    output_dir: &Path,
+    force: bool,
+) {
+    for def_id in server::function_ids(tcx) {
+        let path = tcx.def_path_str(def_id);
+        if !tcx.is_mir_available(def_id) {
+            info!("skipping {}, it has no mir", path);
+            continue;
+        }
+        let mut pass = Translator::new(
+            tcx,
+            tcx.crate_name(LOCAL_CRATE).to_string(),
+            None,
+            options.clone(),
+        )
+        .unwrap_or_else(|err| {
+            error!("unable to create the translator: {}", err);
+            std::process::exit(1)
+        });
+        if let Err(err) = pass.petrify(def_id) {
+            error!("translation of {} failed: {}", path, err);
+            continue;
+        }
+        let file_name: String = path
+            .replace("::", ".")
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        for format in formats {
+            let file_path = output_dir.join(format!("{}.{}", file_name, format.extension()));
+            write_file(pass.net(), *format, pass.node_map(), &file_path, force);
+        }
+    }
+}
+
+fn write_net<W: Write>(
+    net: &Net,
+    format: Format,
+    nodes: &[NodeSource],
+    writer: &mut W,
+) -> Result<(), String> {
+    info!("generating {}", format.extension());
+    match format {
+        Format::Pnml => net
+            .petri_net()
+            .to_pnml(writer)
+            .map_err(|err| format!("{:?}", err)),
+        Format::Lola => net
+            .petri_net()
+            .to_lola(writer)
+            .map_err(|err| format!("{:?}", err)),
+        // clustered by function, unlike the writer of the net library
+        Format::Dot => dot::write(net, &mut *writer).map_err(|err| err.to_string()),
+        Format::Json => JsonNet::read(net, nodes)
+            .write(&mut *writer)
+            .map_err(|err| err.to_string()),
+    }
+}
+
+fn write_file(net: &Net, format: Format, nodes: &[NodeSource], path: &Path, force: bool) {
+    let mut file = out_file(path, force);
+    // model checkers must not read a truncated file
+    let written = write_net(net, format, nodes, &mut file)
+        .and_then(|_| file.sync_all().map_err(|err| err.to_string()));
+    if let Err(err) = written {
+        error!("unable to write the {} net: {}", format.extension(), err);
+        std::process::exit(1)
+    }
+}
+
+fn write_stdout(net: &Net, format: Format, nodes: &[NodeSource]) {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let written = write_net(net, format, nodes, &mut stdout)
+        .and_then(|_| stdout.flush().map_err(|err| err.to_string()));
+    if let Err(err) = written {
+        error!("unable to write the {} net: {}", format.extension(), err);
+        std::process::exit(1)
+    }
+}
+
+fn exit_with_candidates(tcx: TyCtxt<'_>) -> ! {
+    error!("candidates are:");
+    for function in server::functions(tcx) {
+        error!("    {}", function);
+    }
+    std::process::exit(1)
+}
+
+fn out_file(path: &Path, overwrite: bool) -> std::fs::File {
+    if path.exists() && !overwrite {
+        error!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        );
+        std::process::exit(1);
+    }
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                error!("unable to create {}: {}", parent.display(), err);
+                std::process::exit(1)
+            }
+        }
+    }
+    match std::fs::File::create(path) {
         Ok(file) => file,
         Err(err) => {
-            panic!("Unable to create file: {}", err);
+            error!("unable to create {}: {}", path.display(), err);
+            std::process::exit(1)
         }
     }
 }
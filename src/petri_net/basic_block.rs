@@ -1,12 +1,19 @@
-use crate::petri_net::function::{op_to_data_node, place_to_data_node, VirtualMemory};
-use petri_to_star::{NodeRef, PetriNet, Result};
+use crate::error::Result;
+use crate::petri_net::function::{
+    index_data_nodes, op_to_data_node, place_to_data_node, VirtualMemory,
+};
+use crate::petri_net::net::Net;
+use petri_to_star::NodeRef;
 use rustc::mir;
 use std::clone::Clone;
+use std::collections::HashSet;
 
 #[derive(Debug)]
 pub struct BasicBlock {
     start_place: NodeRef,
     end_place: NodeRef,
+    // the end place of a coarse block is only added if it has statements
+    coarse: bool,
     pub statements: Vec<Statement>,
 }
 
@@ -14,22 +21,41 @@ pub struct BasicBlock {
 pub struct Statement {
     start_place: NodeRef,
     stmt_transition: NodeRef,
+    // transitions that can fire instead of the statement transition
+    alternatives: Vec<NodeRef>,
+    // the data places the transition already reads and writes
+    accessed: HashSet<NodeRef>,
+    // if further statements without side effects can be folded into the transition
+    foldable: bool,
 }
 
 impl BasicBlock {
-    pub fn new<'net>(net: &'net mut PetriNet, start_place: NodeRef) -> Result<Self> {
+    pub fn new<'net>(net: &'net mut Net, start_place: NodeRef) -> Result<Self> {
         let end_place = net.add_place();
         let statements = Vec::new();
         Ok(BasicBlock {
             start_place,
             end_place,
+            coarse: false,
             statements,
         })
     }
 
+    /// A block that folds its statements (see `fold_statement`).
+    /// Start and end are the same place until a statement is added,
+    /// so without statements the terminator leaves the block directly.
+    pub fn new_coarse(start_place: NodeRef) -> Self {
+        BasicBlock {
+            start_place,
+            end_place: start_place,
+            coarse: true,
+            statements: Vec::new(),
+        }
+    }
+
     pub fn add_statement<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         statement: &mir::Statement<'_>,
         virt_memory: &VirtualMemory,
     ) -> Result<()> {
@@ -38,7 +64,7 @@ impl BasicBlock {
         let start_place = {
             if let Some(statement) = self.statements.last() {
                 let place = net.add_place();
-                net.add_arc(statement.stmt_transition, place)?;
+                statement.connect(net, place)?;
                 place
             } else {
                 self.start_place()
@@ -50,17 +76,51 @@ impl BasicBlock {
         Ok(())
     }
 
-    pub fn finish_statement_block(&self, net: &mut PetriNet) -> Result<()> {
+    /// Adds the data access of a statement without side effects to the transition
+    /// of the previous statement if that has no side effects either.
+    /// Other statements get their own transition.
+    /// Returns if a new transition was added.
+    pub fn fold_statement<'net>(
+        &mut self,
+        net: &'net mut Net,
+        statement: &mir::Statement<'_>,
+        virt_memory: &VirtualMemory,
+    ) -> Result<bool> {
+        match self.statements.last_mut() {
+            Some(last) if last.foldable && is_foldable(statement) => {
+                last.build(net, statement, virt_memory)?;
+                Ok(false)
+            }
+            _ => {
+                self.add_statement(net, statement, virt_memory)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Returns the transition that connects a block without statements (to be named)
+    pub fn finish_statement_block(&mut self, net: &mut Net) -> Result<Option<NodeRef>> {
         if let Some(statement) = self.statements.last() {
-            net.add_arc(statement.stmt_transition, self.end_place)?;
-        } else {
+            if self.coarse {
+                self.end_place = net.add_place();
+            }
+            statement.connect(net, self.end_place)?;
+        } else if !self.coarse {
             // if there is only a terminator (no statement) we have to connect start and end place of the block
             let t = net.add_transition();
-            t.name(net, "NOP".into())?;
             net.add_arc(self.start_place, t)?;
             net.add_arc(t, self.end_place)?;
+            return Ok(Some(t));
         }
-        Ok(())
+        Ok(None)
+    }
+
+    pub fn last_statement(&self) -> Option<&Statement> {
+        self.statements.last()
+    }
+
+    pub fn last_statement_mut(&mut self) -> Option<&mut Statement> {
+        self.statements.last_mut()
     }
 
     pub fn start_place(&self) -> NodeRef {
@@ -73,7 +133,7 @@ impl BasicBlock {
 
 impl Statement {
     pub fn new<'net>(
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         start_place: NodeRef,
         statement: &mir::Statement<'_>,
         virt_memory: &VirtualMemory,
@@ -81,12 +141,15 @@ impl Statement {
         // the statement transition is its important part
         // it "executes" the effect of the statement
         let stmt_transition = net.add_transition();
-        stmt_transition.name(net, format!("{:?}", statement.kind))?;
-        //stmt_transition.name(net, "");
+        net.name(stmt_transition, format!("{:?}", statement.kind))?;
+        //net.name(stmt_transition, "");
         net.add_arc(start_place, stmt_transition)?;
-        let stmt = Statement {
+        let mut stmt = Statement {
             start_place: start_place.clone(),
             stmt_transition,
+            alternatives: Vec::new(),
+            accessed: HashSet::new(),
+            foldable: is_foldable(statement),
         };
         stmt.build(net, statement, virt_memory)?;
         Ok(stmt)
@@ -95,9 +158,31 @@ impl Statement {
         &self.start_place
     }
 
+    pub fn transition(&self) -> NodeRef {
+        self.stmt_transition
+    }
+
+    /// Adds a transition that shares the start and end place with the statement transition
+    pub fn add_alternative(&mut self, net: &mut Net, name: String) -> Result<NodeRef> {
+        let t = net.add_transition();
+        net.name(t, name)?;
+        net.add_arc(self.start_place, t)?;
+        self.alternatives.push(t);
+        Ok(t)
+    }
+
+    // connect the statement to the place that follows it
+    fn connect(&self, net: &mut Net, place: NodeRef) -> Result<()> {
+        net.add_arc(self.stmt_transition, place)?;
+        for alternative in &self.alternatives {
+            net.add_arc(*alternative, place)?;
+        }
+        Ok(())
+    }
+
     fn build<'net>(
-        &self,
-        net: &'net mut PetriNet,
+        &mut self,
+        net: &'net mut Net,
         statement: &mir::Statement<'_>,
         virt_memory: &VirtualMemory,
     ) -> Result<()> {
@@ -106,32 +191,17 @@ impl Statement {
             StatementKind::Assign(box (lvalue, rvalue)) => {
                 self.build_assign(net, virt_memory, lvalue, rvalue)?
             }
-            StatementKind::StorageLive(local) => {
-                let local = virt_memory.get_local(&local).expect("local not found");
-                net.add_arc(
-                    local.prenatal_place.expect("no uninitialized place"),
-                    self.stmt_transition,
-                )?;
-                net.add_arc(self.stmt_transition, local.live_place)?;
-            }
-            StatementKind::StorageDead(local) => {
-                let local = virt_memory.get_local(&local).expect("local not found");
-                net.add_arc(local.live_place, self.stmt_transition)?;
-                net.add_arc(
-                    self.stmt_transition,
-                    local.dead_place.expect("no dead place"),
-                )?;
-            }
-            StatementKind::SetDiscriminant { place, .. } => {
-                let place_node = place_to_data_node(place, virt_memory);
-                net.add_arc(place_node, self.stmt_transition)?;
-                net.add_arc(self.stmt_transition, place_node)?;
+            // the lifetime of locals is modeled by the function (`storage_live`, `storage_dead`)
+            StatementKind::StorageLive(_) | StatementKind::StorageDead(_) => {}
+            // modeled by the function to remember the variant (`set_discriminant`)
+            StatementKind::SetDiscriminant { .. } => {}
+            // Stacked Borrows artifacts and type ascriptions have no runtime effect
+            StatementKind::Retag(_, _) | StatementKind::AscribeUserType(box (_, _), _) => {
+                trace!("ignoring {:?}", statement.kind)
             }
-            StatementKind::FakeRead(_, _)
-            | StatementKind::InlineAsm(_)
-            | StatementKind::Retag(_, _)
-            | StatementKind::AscribeUserType(box (_, _), _) => {
-                panic!("statementKind not supported: {:?}", statement.kind)
+            // the translator skips fake reads and keeps inline assembly opaque
+            StatementKind::FakeRead(_, _) | StatementKind::InlineAsm(_) => {
+                trace!("no data access for {:?}", statement.kind)
             }
             StatementKind::Nop => {}
         }
@@ -139,70 +209,107 @@ impl Statement {
     }
 
     fn build_assign<'net>(
-        &self,
-        net: &'net mut PetriNet,
+        &mut self,
+        net: &'net mut Net,
         virt_memory: &VirtualMemory,
         lvalue: &mir::Place<'_>,
         rvalue: &mir::Rvalue<'_>,
     ) -> Result<()> {
         use mir::Rvalue;
-        let llocal = place_to_data_node(lvalue, virt_memory);
-        add_node_to_statement(net, llocal, self.stmt_transition)?;
+        let llocal = place_to_data_node(lvalue, virt_memory)?;
+        self.access(net, llocal)?;
+        for index in index_data_nodes(lvalue, virt_memory)? {
+            self.access(net, index)?;
+        }
+        if let Some(place) = rvalue_place(rvalue) {
+            for index in index_data_nodes(place, virt_memory)? {
+                self.access(net, index)?;
+            }
+        }
         match rvalue {
             Rvalue::Use(ref operand)
             | Rvalue::Repeat(ref operand, _)
             | Rvalue::UnaryOp(_, ref operand) => {
-                let op_place = op_to_data_node(operand, virt_memory);
-                add_node_to_statement(net, op_place, self.stmt_transition)?;
+                let op_place = op_to_data_node(operand, virt_memory)?;
+                self.access(net, op_place)?;
             }
             Rvalue::Ref(_, _, ref place) | Rvalue::Len(ref place) => {
-                let place_local = place_to_data_node(place, virt_memory);
-                add_node_to_statement(net, place_local, self.stmt_transition)?;
+                let place_local = place_to_data_node(place, virt_memory)?;
+                self.access(net, place_local)?;
             }
             Rvalue::Cast(ref _kind, ref operand, ref _typ) => {
-                let op_place = op_to_data_node(operand, virt_memory);
-                add_node_to_statement(net, op_place, self.stmt_transition)?;
+                let op_place = op_to_data_node(operand, virt_memory)?;
+                self.access(net, op_place)?;
             }
             Rvalue::BinaryOp(ref _operator, ref loperand, ref roperand)
             | Rvalue::CheckedBinaryOp(ref _operator, ref loperand, ref roperand) => {
-                let lop_place = op_to_data_node(loperand, virt_memory);
-                let rop_place = op_to_data_node(roperand, virt_memory);
-                add_node_to_statement(net, lop_place, self.stmt_transition)?;
-                add_node_to_statement(net, rop_place, self.stmt_transition)?;
+                let lop_place = op_to_data_node(loperand, virt_memory)?;
+                let rop_place = op_to_data_node(roperand, virt_memory)?;
+                self.access(net, lop_place)?;
+                self.access(net, rop_place)?;
             }
             Rvalue::NullaryOp(ref operator, ref _typ) => match operator {
                 // these are essentially a lookup of the type size in the static space
                 mir::NullOp::SizeOf | mir::NullOp::Box => {
-                    net.add_arc(virt_memory.get_constant(), self.stmt_transition)?;
-                    net.add_arc(self.stmt_transition, virt_memory.get_constant())?;
+                    self.access(net, virt_memory.get_constant())?
                 }
             },
             Rvalue::Discriminant(ref place) => {
-                let op_place = place_to_data_node(place, virt_memory);
-                add_node_to_statement(net, op_place, self.stmt_transition)?;
+                let op_place = place_to_data_node(place, virt_memory)?;
+                self.access(net, op_place)?;
             }
             Rvalue::Aggregate(ref _kind, ref operands) => {
                 //FIXME: does the kind matter?
                 for operand in operands {
-                    let op_place = op_to_data_node(operand, virt_memory);
-                    add_node_to_statement(net, op_place, self.stmt_transition)?;
+                    let op_place = op_to_data_node(operand, virt_memory)?;
+                    self.access(net, op_place)?;
                 }
             }
             Rvalue::AddressOf(_, place) => {
-                let place_local = place_to_data_node(place, virt_memory);
-                add_node_to_statement(net, place_local, self.stmt_transition)?;
+                let place_local = place_to_data_node(place, virt_memory)?;
+                self.access(net, place_local)?;
             }
         }
         Ok(())
     }
+
+    // reads and writes the data place (once for all folded statements)
+    fn access(&mut self, net: &mut Net, place_node: NodeRef) -> Result<()> {
+        if self.accessed.insert(place_node) {
+            net.add_arc(place_node, self.stmt_transition)?;
+            net.add_arc(self.stmt_transition, place_node)?;
+        }
+        Ok(())
+    }
 }
 
-fn add_node_to_statement(
-    net: &mut PetriNet,
-    place_node: NodeRef,
-    statement_transition: NodeRef,
-) -> Result<()> {
-    net.add_arc(place_node, statement_transition)?;
-    net.add_arc(statement_transition, place_node)?;
-    Ok(())
+// If the statement only accesses data
+// (the lifetime of locals, discriminants and inline assembly have further effects)
+fn is_foldable(statement: &mir::Statement<'_>) -> bool {
+    use mir::StatementKind;
+    match statement.kind {
+        StatementKind::Assign(..)
+        | StatementKind::Retag(..)
+        | StatementKind::AscribeUserType(..)
+        | StatementKind::FakeRead(..)
+        | StatementKind::Nop => true,
+        StatementKind::StorageLive(_)
+        | StatementKind::StorageDead(_)
+        | StatementKind::SetDiscriminant { .. }
+        | StatementKind::InlineAsm(_) => false,
+    }
+}
+
+// the place that is read by an rvalue
+fn rvalue_place<'a, 'tcx>(rvalue: &'a mir::Rvalue<'tcx>) -> Option<&'a mir::Place<'tcx>> {
+    use mir::{Operand, Rvalue};
+    match rvalue {
+        Rvalue::Use(Operand::Copy(place))
+        | Rvalue::Use(Operand::Move(place))
+        | Rvalue::Ref(_, _, place)
+        | Rvalue::Len(place)
+        | Rvalue::Discriminant(place)
+        | Rvalue::AddressOf(_, place) => Some(place),
+        _ => None,
+    }
 }
@@ -1,38 +1,41 @@
 use super::basic_block::BasicBlock;
 use super::intrinsics::generic_foreign;
+use super::net::{node_id, Net};
 use super::unique_functions::MutexList;
-use petri_to_star::{NodeRef, PetriNet, PlaceRef, Result};
+use crate::error::{Result, TranslationError};
+use petri_to_star::NodeRef;
 use rustc::mir;
+use rustc_hir::def_id::DefId;
+use rustc_hir::GeneratorKind;
 use rustc_index::vec::IndexVec;
-use std::collections::HashMap;
-use std::convert::TryFrom;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 
+// Only the field of the blocks is borrowed, so the memory of the function stays accessible
 macro_rules! active_block {
     ($function:ident) => {
-        $function
-            .basic_blocks
-            .get(
-                &$function
-                    .active_block
-                    .expect("activeBlock was not initialized"),
-            )
-            .expect("unable to find active basic block in function");
+        match $function.active_block {
+            Some(block) => $function.basic_blocks.get(&block),
+            None => None,
+        }
+        .ok_or_else(|| TranslationError::UnexpectedMir("no active basic block".into()))?
     };
 }
 
 macro_rules! active_block_mut {
     ($function:ident) => {
-        $function
-            .basic_blocks
-            .get_mut(
-                &$function
-                    .active_block
-                    .expect("activeBlock was not initialized"),
-            )
-            .expect("unable to find active basic block in function");
+        match $function.active_block {
+            Some(block) => $function.basic_blocks.get_mut(&block),
+            None => None,
+        }
+        .ok_or_else(|| TranslationError::UnexpectedMir("no active basic block".into()))?
     };
 }
 
+fn statement_not_added() -> TranslationError {
+    TranslationError::UnexpectedMir("the statement was not added to its block".into())
+}
+
 macro_rules! block_to_start_place {
     ($function:ident, $net:ident, $block:expr) => {
         match $function.basic_blocks.get(&$block) {
@@ -49,14 +52,40 @@ macro_rules! block_to_start_place {
 #[derive(Debug)]
 pub struct VirtualMemory {
     locals: HashMap<mir::Local, Data>,
+    // reborrow temporaries (`_2 = &mut (*_1)`) that are transparent aliases of another local
+    // data access to the key is redirected to the value, storage statements are not
+    aliases: HashMap<mir::Local, mir::Local>,
     //FIXME: this is an oversimplification of statics
     // the DefId can be of an entire function and
     // inlining may split the same static into different DefIds
     statics: HashMap<mir::Promoted, Data>,
+    // `static` items, they are shared by all functions
+    globals: HashMap<DefId, NodeRef>,
     // constants currently don't need special data and can be represented all with the same node
     constants: Data,
 }
 
+/// How fine grained the control flow of a function is modeled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    /// a transition for every statement
+    Statement,
+    /// a transition for every terminator, a run of statements without side effects
+    /// shares one transition
+    Block,
+    /// like `Block` but a goto into a new block merges it with its predecessor,
+    /// so apart from the folded statements only branches, calls and synchronization
+    /// remain as transitions
+    /// (`--simplify` is an alias, the net cannot be simplified after the translation)
+    Sync,
+}
+
+impl Default for Granularity {
+    fn default() -> Self {
+        Granularity::Statement
+    }
+}
+
 #[derive(Debug)]
 pub struct Function<'mir> {
     pub name: String,
@@ -66,6 +95,36 @@ pub struct Function<'mir> {
     pub active_block: Option<mir::BasicBlock>,
     start_place: NodeRef,
     return_flow: NodeRef,
+    unwind_flow: NodeRef,
+    // variant indices of enum locals that are known in the active block from SetDiscriminant
+    discriminants: HashMap<mir::Local, usize>,
+    // values of locals that were assigned a constant in the active block
+    // (only booleans, integers and discriminants, the values are the bits of the constant)
+    known_values: HashMap<mir::Local, u128>,
+    // locals that hold a pointer to a known function
+    fn_pointers: HashMap<mir::Local, DefId>,
+    // locals that hold the `(result, overflowed)` tuple of a CheckedBinaryOp
+    checked_results: HashSet<mir::Local>,
+    // with a coarse granularity the statements of a block are folded
+    granularity: Granularity,
+    // source location of the statement or terminator that is translated
+    // (only set if transitions should be annotated)
+    source_location: Option<String>,
+    // the span of the statement or terminator for the node map (only set if it is recorded)
+    span: Option<String>,
+    // the origin of every named transition if the node map is recorded
+    nodes: Option<Vec<NodeSource>>,
+}
+
+/// Where a transition of the net comes from
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeSource {
+    /// the id of the node (the same as in the JSON export)
+    pub node: String,
+    pub name: String,
+    pub function: String,
+    pub block: Option<usize>,
+    pub span: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -90,18 +149,16 @@ pub enum LocalKey {
 }
 
 impl Local {
-    pub fn new<'net>(net: &'net mut PetriNet, name: &str) -> Result<Self> {
+    pub fn new<'net>(net: &'net mut Net, name: &str) -> Result<Self> {
         let prenatal_place = Some(net.add_place());
-        PlaceRef::try_from(prenatal_place.unwrap())?.marking(net, 1)?;
+        net.mark(prenatal_place.unwrap(), 1)?;
         let live_place = net.add_place();
         let dead_place = Some(net.add_place());
-        prenatal_place
-            .unwrap()
-            .name(net, format!("{} uninitialized", name))?;
-        live_place.name(net, format!("{} live", name))?;
+        net.name(prenatal_place.unwrap(), format!("{} uninitialized", name))?;
+        net.name(live_place, format!("{} live", name))?;
         //FIXME: remove this line when https://github.com/rust-lang/rust/issues/67400 gets fixed
-        PlaceRef::try_from(live_place)?.marking(net, 1)?;
-        dead_place.unwrap().name(net, format!("{} dead", name))?;
+        net.mark(live_place, 1)?;
+        net.name(dead_place.unwrap(), format!("{} dead", name))?;
         Ok(Local {
             prenatal_place,
             live_place,
@@ -127,6 +184,12 @@ impl VirtualMemory {
     }
 
     pub fn get_local(&self, local: &mir::Local) -> Option<&Local> {
+        self.get_storage_local(&self.resolve_alias(*local))
+    }
+
+    /// Like `get_local` but ignores aliases.
+    /// The lifetime (StorageLive/StorageDead) of a reborrow temporary is still its own.
+    pub fn get_storage_local(&self, local: &mir::Local) -> Option<&Local> {
         match self.locals.get(local) {
             Some(Data::Local(local)) => Some(local),
             None => None,
@@ -134,6 +197,27 @@ impl VirtualMemory {
         }
     }
 
+    /// Like `get_local` but a local that is not in the function is an error
+    pub fn find_local(&self, local: &mir::Local) -> Result<&Local> {
+        self.get_local(local)
+            .ok_or_else(|| TranslationError::UnexpectedMir(format!("local {:?} not found", local)))
+    }
+
+    fn resolve_alias(&self, local: mir::Local) -> mir::Local {
+        match self.aliases.get(&local) {
+            Some(aliased) => *aliased,
+            None => local,
+        }
+    }
+
+    fn add_alias(&mut self, alias: mir::Local, local: mir::Local) {
+        let local = self.resolve_alias(local);
+        if alias != local {
+            debug!("{:?} is a reborrow of {:?}", alias, local);
+            self.aliases.insert(alias, local);
+        }
+    }
+
     pub fn get_static(&self, statik: &mir::Promoted) -> Option<NodeRef> {
         match self.statics.get(statik) {
             Some(Data::Static(statik)) => Some(*statik),
@@ -142,27 +226,58 @@ impl VirtualMemory {
         }
     }
 
+    /// Like `get_static` but a promoted that is not in the function is an error
+    pub fn find_static(&self, statik: &mir::Promoted) -> Result<NodeRef> {
+        self.get_static(statik).ok_or_else(|| {
+            TranslationError::UnexpectedMir(format!("promoted {:?} not found", statik))
+        })
+    }
+
+    pub fn get_global(&self, statik: &DefId) -> Option<NodeRef> {
+        self.globals.get(statik).copied()
+    }
+
     pub fn get_constant(&self) -> NodeRef {
         match &self.constants {
             Data::Constant(constant) => *constant,
             _ => panic!("Non constant stored in constant space"),
         }
     }
+
+    /// The place of every local (the live place for locals with storage)
+    pub fn local_places(&self) -> Vec<(mir::Local, NodeRef)> {
+        let mut locals: Vec<_> = self
+            .locals
+            .iter()
+            .map(|(index, data)| {
+                let place = match data {
+                    Data::Local(local) => local.live_place,
+                    Data::Static(node) | Data::Constant(node) => *node,
+                };
+                (*index, place)
+            })
+            .collect();
+        locals.sort_by_key(|(index, _)| *index);
+        locals
+    }
 }
 
 impl<'mir> Function<'mir> {
     pub fn new<'net>(
         name: String,
         mir_body: &'mir mir::BodyAndCache<'mir>,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         mut args: Vec<Local>, // data that is used from the previous stack frame
         data_return: Local,   // node which stores the return value
         start_place: NodeRef, // where to start from
         constant_memory: &Data,
         static_memory: &HashMap<mir::Promoted, Data>,
-        return_flow: NodeRef, // where to continue after the call
+        global_memory: &HashMap<DefId, NodeRef>, // places of the statics used in the body
+        return_flow: NodeRef,                    // where to continue after the call
+        unwind_flow: NodeRef, // where to continue if the function unwinds (Resume)
         mutex_list: &mut MutexList,
         tcx: rustc::ty::TyCtxt<'mir>,
+        granularity: Granularity,
     ) -> Result<Self> {
         let mut function = Function {
             name,
@@ -171,15 +286,35 @@ impl<'mir> Function<'mir> {
             //FIXME: unnessecary cloning of statics and constants
             virt_memory: VirtualMemory {
                 locals: HashMap::new(),
+                aliases: HashMap::new(),
                 constants: constant_memory.clone(),
                 statics: static_memory.clone(),
+                globals: global_memory.clone(),
             },
             active_block: None,
             start_place,
             return_flow,
+            unwind_flow,
+            discriminants: HashMap::new(),
+            known_values: HashMap::new(),
+            fn_pointers: HashMap::new(),
+            checked_results: HashSet::new(),
+            granularity,
+            source_location: None,
+            span: None,
+            nodes: None,
         };
         // add the locals but remember the locals from the previous stack frame
         // index zero is the return local followed by the function arguments
+        // (`_1` to `_{arg_count}` in the callee are the caller locals in `args`)
+        if args.len() != mir_body.arg_count {
+            warn!(
+                "{} takes {} arguments but is called with {}",
+                function.name,
+                mir_body.arg_count,
+                args.len()
+            );
+        }
         args.insert(0, data_return);
         function.add_locals(net, &function.mir_body.local_decls, args, mutex_list, tcx)?;
         Ok(function)
@@ -187,27 +322,341 @@ impl<'mir> Function<'mir> {
 
     pub fn add_statement<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
+        statement: &mir::Statement<'_>,
+    ) -> Result<()> {
+        let added = match self.granularity {
+            Granularity::Statement => {
+                active_block_mut!(self).add_statement(net, statement, &self.virt_memory)?;
+                true
+            }
+            Granularity::Block | Granularity::Sync => {
+                active_block_mut!(self).fold_statement(net, statement, &self.virt_memory)?
+            }
+        };
+        // a folded statement keeps the name of the first statement of the transition
+        if added && self.source_location.is_some() {
+            let name = self.label(format!("{:?}", statement.kind));
+            let statement = active_block!(self)
+                .last_statement()
+                .ok_or_else(statement_not_added)?;
+            net.name(statement.transition(), name)?;
+        }
+        if added && self.nodes.is_some() {
+            let t = active_block!(self)
+                .last_statement()
+                .ok_or_else(statement_not_added)?
+                .transition();
+            self.record(t, format!("{:?}", statement.kind));
+        }
+        // the reborrow itself is still a normal assignment, only later accesses are redirected
+        if let mir::StatementKind::Assign(box (place, rvalue)) = &statement.kind {
+            if let Some((alias, local)) = reborrow(place, rvalue) {
+                self.virt_memory.add_alias(alias, local);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a statement with effects that are not modeled (e.g. inline assembly),
+    /// its transition is labeled as opaque.
+    pub fn add_opaque_statement(
+        &mut self,
+        net: &mut Net,
         statement: &mir::Statement<'_>,
+        name: &str,
     ) -> Result<()> {
         active_block_mut!(self).add_statement(net, statement, &self.virt_memory)?;
+        let t = active_block!(self)
+            .last_statement()
+            .ok_or_else(statement_not_added)?
+            .transition();
+        self.name_transition(net, t, format!("opaque {}", name))
+    }
+
+    /// Marks the live place of a local (StorageLive has to be the last added statement).
+    /// A local can be live again after it died in a previous loop iteration, so an
+    /// alternative transition revives it from its dead place.
+    pub fn storage_live(&mut self, net: &mut Net, local: &mir::Local) -> Result<()> {
+        let local = *self.virt_memory.get_storage_local(local).ok_or_else(|| {
+            TranslationError::UnexpectedMir(format!("StorageLive of unknown local {:?}", local))
+        })?;
+        let (prenatal_place, dead_place) = match (local.prenatal_place, local.dead_place) {
+            (Some(prenatal_place), Some(dead_place)) => (prenatal_place, dead_place),
+            _ => {
+                return Err(TranslationError::UnexpectedMir(
+                    "StorageLive of a local without lifetime".into(),
+                ))
+            }
+        };
+        let statement = active_block_mut!(self)
+            .last_statement_mut()
+            .ok_or_else(|| TranslationError::UnexpectedMir("no StorageLive statement".into()))?;
+        let t = statement.transition();
+        net.add_arc(prenatal_place, t)?;
+        net.add_arc(t, local.live_place)?;
+        let revive = statement.add_alternative(net, "StorageLive (revive)".into())?;
+        net.add_arc(dead_place, revive)?;
+        net.add_arc(revive, local.live_place)?;
+        self.rename_transition(net, revive, "StorageLive (revive)".into())?;
         Ok(())
     }
 
-    pub fn finish_basic_block(&self, net: &mut PetriNet) -> Result<()> {
-        active_block!(self).finish_statement_block(net)
+    /// Moves the token of a local from its live to its dead place
+    /// (StorageDead has to be the last added statement).
+    /// Returns the transition so that the end of the lifetime can have further effects.
+    pub fn storage_dead(&mut self, net: &mut Net, local: &mir::Local) -> Result<NodeRef> {
+        let local = *self.virt_memory.get_storage_local(local).ok_or_else(|| {
+            TranslationError::UnexpectedMir(format!("StorageDead of unknown local {:?}", local))
+        })?;
+        let dead_place = local.dead_place.ok_or_else(|| {
+            TranslationError::UnexpectedMir("StorageDead of a local without lifetime".into())
+        })?;
+        let t = active_block!(self)
+            .last_statement()
+            .ok_or_else(|| TranslationError::UnexpectedMir("no StorageDead statement".into()))?
+            .transition();
+        net.add_arc(local.live_place, t)?;
+        net.add_arc(t, dead_place)?;
+        Ok(t)
+    }
+
+    /// Writes the enum place (SetDiscriminant has to be the last added statement).
+    /// The net does not hold values, the variant is only tracked (see `set_variant`).
+    pub fn set_discriminant(&mut self, net: &mut Net, place: &mir::Place<'_>) -> Result<()> {
+        let place_node = place_to_data_node(place, &self.virt_memory)?;
+        let t = active_block!(self)
+            .last_statement()
+            .ok_or_else(|| TranslationError::UnexpectedMir("no SetDiscriminant statement".into()))?
+            .transition();
+        net.add_arc(place_node, t)?;
+        net.add_arc(t, place_node)?;
+        Ok(())
     }
 
-    pub fn goto<'net>(&mut self, net: &'net mut PetriNet, to: mir::BasicBlock) -> Result<()> {
+    /// Remembers the variant of the enum place for later reads of the discriminant
+    /// (`None` forgets it).
+    /// Only places without projection are tracked, the variant of others is unknown.
+    pub fn set_variant(&mut self, place: &mir::Place<'_>, variant_index: Option<usize>) {
+        match (&place.base, place.projection.is_empty(), variant_index) {
+            (mir::PlaceBase::Local(local), true, Some(variant_index)) => {
+                self.discriminants.insert(*local, variant_index);
+            }
+            (mir::PlaceBase::Local(local), true, None) => {
+                self.discriminants.remove(local);
+            }
+            _ => debug!("untracked discriminant of {:?}", place),
+        }
+    }
+
+    /// The variant of an enum place if it was set before in the active block
+    pub fn variant(&self, place: &mir::Place<'_>) -> Option<usize> {
+        match (&place.base, place.projection.is_empty()) {
+            (mir::PlaceBase::Local(local), true) => self.discriminants.get(local).copied(),
+            _ => None,
+        }
+    }
+
+    /// Remembers the value of a local for the rest of the active block (`None` forgets it)
+    pub fn set_known_value(&mut self, local: mir::Local, value: Option<u128>) {
+        match value {
+            Some(value) => self.known_values.insert(local, value),
+            None => self.known_values.remove(&local),
+        };
+    }
+
+    /// Forgets all values, e.g. if memory is written through a pointer
+    pub fn forget_known_values(&mut self) {
+        self.known_values.clear();
+        self.discriminants.clear();
+    }
+
+    /// The value of the operand if it is a local with a known value
+    pub fn known_value(&self, operand: &mir::Operand<'_>) -> Option<u128> {
+        match operand {
+            mir::Operand::Copy(place) | mir::Operand::Move(place) => {
+                match (&place.base, place.projection.is_empty()) {
+                    (mir::PlaceBase::Local(local), true) => self.known_values.get(local).copied(),
+                    _ => None,
+                }
+            }
+            mir::Operand::Constant(_) => None,
+        }
+    }
+
+    /// If the active block is only executed while unwinding
+    pub fn in_cleanup_block(&self) -> bool {
+        match self.active_block {
+            Some(block) => self.mir_body.basic_blocks()[block].is_cleanup,
+            None => false,
+        }
+    }
+
+    pub fn finish_basic_block(&mut self, net: &mut Net) -> Result<()> {
+        if let Some(nop) = active_block_mut!(self).finish_statement_block(net)? {
+            self.name_transition(net, nop, "NOP".into())?;
+        }
+        Ok(())
+    }
+
+    pub fn goto<'net>(&mut self, net: &'net mut Net, to: mir::BasicBlock) -> Result<()> {
+        // the goto transition would only move the token, so the new block just continues on
+        // the end place of the active block (this is impossible if the block already exists)
+        if self.granularity == Granularity::Sync && !self.basic_blocks.contains_key(&to) {
+            let start_place = active_block!(self).end_place();
+            self.basic_blocks
+                .insert(to, BasicBlock::new_coarse(start_place))
+                .expect_none("block already exists");
+            return Ok(());
+        }
         let t = net.add_transition();
-        t.name(net, "Goto".into())?;
+        self.name_transition(net, t, "Goto".into())?;
         net.add_arc(active_block!(self).end_place(), t)?;
         let to = block_to_start_place!(self, net, to);
         net.add_arc(t, to)?;
         Ok(())
     }
 
-    pub fn retorn<'net>(&mut self, net: &'net mut PetriNet) -> Result<()> {
+    /// Suspends the generator until it is resumed in the `resume` block
+    /// or dropped in the `drop` block.
+    /// In the generator of an async function or block every suspension is an `.await`.
+    pub fn generator_yield(
+        &mut self,
+        net: &mut Net,
+        resume: mir::BasicBlock,
+        drop: Option<mir::BasicBlock>,
+    ) -> Result<()> {
+        let (state, transition) = match self.mir_body.generator_kind {
+            Some(GeneratorKind::Async(_)) => ("pending", "await"),
+            _ => ("suspended", "Yield"),
+        };
+        let suspended = net.add_place();
+        net.name(suspended, format!("{} {}", self.name, state))?;
+        let t = net.add_transition();
+        self.name_transition(net, t, transition.into())?;
+        net.add_arc(active_block!(self).end_place(), t)?;
+        net.add_arc(t, suspended)?;
+        let resume = block_to_start_place!(self, net, resume);
+        let t_resume = net.add_transition();
+        self.name_transition(net, t_resume, "resume".into())?;
+        net.add_arc(suspended, t_resume)?;
+        net.add_arc(t_resume, resume)?;
+        if let Some(drop) = drop {
+            let drop = block_to_start_place!(self, net, drop);
+            let t_drop = net.add_transition();
+            self.name_transition(net, t_drop, "generator drop".into())?;
+            net.add_arc(suspended, t_drop)?;
+            net.add_arc(t_drop, drop)?;
+        }
+        Ok(())
+    }
+
+    /// One token continues in the given block, one is put on the start place of a new thread
+    pub fn fork(
+        &mut self,
+        net: &mut Net,
+        continuation: mir::BasicBlock,
+        thread_start: NodeRef,
+    ) -> Result<()> {
+        let continuation = block_to_start_place!(self, net, continuation);
+        let t = net.add_transition();
+        self.name_transition(net, t, "spawn".into())?;
+        net.add_arc(active_block!(self).end_place(), t)?;
+        net.add_arc(t, continuation)?;
+        net.add_arc(t, thread_start)?;
+        Ok(())
+    }
+
+    pub fn add_fn_pointer(&mut self, local: mir::Local, function: DefId) {
+        if let Some(old) = self.fn_pointers.insert(local, function) {
+            if old != function {
+                // different values in different branches cannot be distinguished
+                debug!("function pointer {:?} is reassigned", local);
+            }
+        }
+    }
+
+    pub fn fn_pointer(&self, local: &mir::Local) -> Option<DefId> {
+        self.fn_pointers.get(local).copied()
+    }
+
+    pub fn add_checked_result(&mut self, local: mir::Local) {
+        self.checked_results.insert(local);
+    }
+
+    /// If the condition is the overflow flag of a CheckedBinaryOp (`_5.1`)
+    pub fn is_overflow_check(&self, cond: &mir::Operand<'_>) -> bool {
+        match cond {
+            mir::Operand::Copy(place) | mir::Operand::Move(place) => match &place.base {
+                mir::PlaceBase::Local(local) => self.checked_results.contains(local),
+                _ => false,
+            },
+            mir::Operand::Constant(_) => false,
+        }
+    }
+
+    /// A nondeterministic branch from the active block to the given place
+    pub fn branch(&mut self, net: &mut Net, target: NodeRef) -> Result<()> {
+        let t = net.add_transition();
+        self.name_transition(net, t, "branch".into())?;
+        net.add_arc(active_block!(self).end_place(), t)?;
+        net.add_arc(t, target)?;
+        Ok(())
+    }
+
+    /// The unwinding path of a call that is not translated into its own subnet.
+    /// With a cleanup block the call may nondeterministically continue there instead of
+    /// returning normally.
+    pub fn call_unwind(
+        &mut self,
+        net: &mut Net,
+        name: &str,
+        cleanup: Option<mir::BasicBlock>,
+    ) -> Result<()> {
+        if let Some(cleanup) = cleanup {
+            let cleanup = block_to_start_place!(self, net, cleanup);
+            let t = net.add_transition();
+            self.name_transition(net, t, format!("unwind_{}", name))?;
+            net.add_arc(active_block!(self).end_place(), t)?;
+            net.add_arc(t, cleanup)?;
+        }
+        Ok(())
+    }
+
+    /// A transition from the active block to the given block that represents a whole call
+    pub fn call_transition(
+        &mut self,
+        net: &mut Net,
+        name: String,
+        continuation: mir::BasicBlock,
+    ) -> Result<NodeRef> {
+        let continuation = block_to_start_place!(self, net, continuation);
+        let t = net.add_transition();
+        self.name_transition(net, t, name)?;
+        net.add_arc(active_block!(self).end_place(), t)?;
+        net.add_arc(t, continuation)?;
+        Ok(t)
+    }
+
+    /// Continues in the given block after the thread with the given end place finished
+    pub fn join(
+        &mut self,
+        net: &mut Net,
+        continuation: mir::BasicBlock,
+        thread_end: Option<NodeRef>,
+    ) -> Result<()> {
+        let continuation = block_to_start_place!(self, net, continuation);
+        let t = net.add_transition();
+        self.name_transition(net, t, "join".into())?;
+        net.add_arc(active_block!(self).end_place(), t)?;
+        if let Some(thread_end) = thread_end {
+            net.add_arc(thread_end, t)?;
+        }
+        net.add_arc(t, continuation)?;
+        Ok(())
+    }
+
+    pub fn retorn<'net>(&mut self, net: &'net mut Net) -> Result<()> {
         let source = {
             // check if we got trolled by an empty function
             if let Some(mir_block) = self.active_block {
@@ -221,77 +670,125 @@ impl<'mir> Function<'mir> {
             }
         };
         let t = net.add_transition();
-        t.name(net, "Return".into())?;
+        self.name_transition(net, t, "Return".into())?;
         net.add_arc(source, t)?;
         net.add_arc(t, self.return_flow)?;
         Ok(())
     }
 
+    /// The end of the drop path of a suspended generator,
+    /// it returns to the function that dropped the generator.
+    pub fn generator_drop(&mut self, net: &mut Net) -> Result<()> {
+        let t = net.add_transition();
+        self.name_transition(net, t, "GeneratorDrop".into())?;
+        net.add_arc(active_block!(self).end_place(), t)?;
+        net.add_arc(t, self.return_flow)?;
+        Ok(())
+    }
+
+    /// The discriminant is not modeled, so every branch can be taken
+    /// unless the index of the only feasible target is `known`.
+    /// The last target is the `otherwise` branch which should only fire if no value matches.
+    /// That would need an inhibitor arc on the discriminant which the net library
+    /// does not offer, so it is a nondeterministic choice as well but named
+    /// "switch otherwise" to tell it apart from the branches of explicit values.
     pub fn switch_int<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         targets: &Vec<mir::BasicBlock>,
+        known: Option<usize>,
     ) -> Result<()> {
-        for bb in targets {
+        for (index, bb) in targets.iter().enumerate() {
+            if known.map_or(false, |known| known != index) {
+                continue;
+            }
             if !self.basic_blocks.contains_key(bb) {
                 self.add_basic_block(net, *bb)?;
             };
             let source_end = active_block!(self).end_place();
             let target_start = self.basic_blocks.get(bb).unwrap().start_place();
             let connection_transition = net.add_transition();
-            connection_transition.name(net, format!("switch int{}", bb.index()))?;
+            let name = if index + 1 == targets.len() {
+                format!("switch otherwise{}", bb.index())
+            } else {
+                format!("switch int{}", bb.index())
+            };
+            self.name_transition(net, connection_transition, name)?;
             net.add_arc(source_end, connection_transition)?;
             net.add_arc(connection_transition, target_start)?;
         }
         Ok(())
     }
 
-    pub fn resume<'net>(&mut self, net: &'net mut PetriNet, unwind_place: NodeRef) -> Result<()> {
-        // TODO: make the unwind and resume semantic clear
+    /// Resume propagates the panic to the caller.
+    /// The unwind flow is the cleanup block of the call site (or the unwind flow of the caller
+    /// if the call has no cleanup). For the entry function it is the global unwind place.
+    pub fn resume<'net>(&mut self, net: &'net mut Net) -> Result<()> {
         let source_place = active_block!(self).end_place();
         let t = net.add_transition();
-        t.name(net, "unwind".into())?;
+        self.name_transition(net, t, "unwind".into())?;
         net.add_arc(source_place, t)?;
-        net.add_arc(t, unwind_place)?;
+        net.add_arc(t, self.unwind_flow)?;
         Ok(())
     }
 
+    /// Where a call from the active block continues if the callee unwinds
+    pub fn unwind_target(
+        &mut self,
+        net: &mut Net,
+        cleanup: Option<mir::BasicBlock>,
+    ) -> Result<NodeRef> {
+        match cleanup {
+            Some(cleanup) => Ok(block_to_start_place!(self, net, cleanup)),
+            None => Ok(self.unwind_flow),
+        }
+    }
+
     pub fn drop<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         target: mir::BasicBlock,
         unwind: Option<mir::BasicBlock>,
-    ) -> Result<()> {
+    ) -> Result<NodeRef> {
         let target_start = block_to_start_place!(self, net, target);
         let source = active_block!(self).end_place().clone();
         let t = net.add_transition();
-        t.name(net, "drop".into())?;
+        self.name_transition(net, t, "drop".into())?;
         net.add_arc(source, t)?;
         net.add_arc(t, target_start)?;
 
         if let Some(unwind) = unwind {
-            let unwind_start = block_to_start_place!(self, net, unwind);
-            let t_unwind = net.add_transition();
-            t_unwind.name(net, "drop_unwind".into())?;
-            net.add_arc(source, t_unwind)?;
-            net.add_arc(t_unwind, unwind_start)?;
+            self.drop_unwind(net, unwind)?;
         };
+        // the drop transition, further effects of the drop can be added to it
+        Ok(t)
+    }
+
+    fn drop_unwind(&mut self, net: &mut Net, unwind: mir::BasicBlock) -> Result<()> {
+        let unwind_start = block_to_start_place!(self, net, unwind);
+        let source = active_block!(self).end_place().clone();
+        let t_unwind = net.add_transition();
+        self.name_transition(net, t_unwind, "drop_unwind".into())?;
+        net.add_arc(source, t_unwind)?;
+        net.add_arc(t_unwind, unwind_start)?;
         Ok(())
     }
 
     pub fn assert(
         &mut self,
-        net: &mut PetriNet,
+        net: &mut Net,
         condition: &mir::Operand<'_>,
         _expected: bool,
         target: mir::BasicBlock,
         cleanup: Option<mir::BasicBlock>,
+        panic_place: Option<NodeRef>, // None if the failing path should be pruned
+        failure_label: String,
     ) -> Result<()> {
         let target_start = block_to_start_place!(self, net, target);
         let source = active_block!(self).end_place().clone();
-        let op_place = op_to_data_node(condition, &self.virt_memory);
+        let op_place = op_to_data_node(condition, &self.virt_memory)?;
         let t = net.add_transition();
-        t.name(net, "assert".into())?;
+        self.name_transition(net, t, "assert".into())?;
         net.add_arc(source, t)?;
         net.add_arc(t, target_start)?;
         net.add_arc(op_place, t)?;
@@ -299,19 +796,23 @@ impl<'mir> Function<'mir> {
         // represents reading the condition
         net.add_arc(t, self.virt_memory.get_constant())?;
         net.add_arc(self.virt_memory.get_constant(), t)?;
-        if let Some(unwind) = cleanup {
-            let unwind_start = block_to_start_place!(self, net, unwind);
+        if let Some(panic_place) = panic_place {
+            // the assertion fails: unwind into the cleanup block or, without cleanup, panic directly
+            let failure_target = match cleanup {
+                Some(unwind) => block_to_start_place!(self, net, unwind),
+                None => panic_place,
+            };
             let t_unwind = net.add_transition();
-            t_unwind.name(net, "assert_unwind".into())?;
+            self.name_transition(net, t_unwind, failure_label)?;
             net.add_arc(source, t_unwind)?;
-            net.add_arc(t_unwind, unwind_start)?;
+            net.add_arc(t_unwind, failure_target)?;
         };
         Ok(())
     }
 
     pub fn emulate_foreign(
         &mut self,
-        net: &mut PetriNet,
+        net: &mut Net,
         intrinsic_name: &str,
         //TODO: check arguments -> are noderefs needed?
         args: &Vec<mir::Operand<'_>>,
@@ -319,19 +820,7 @@ impl<'mir> Function<'mir> {
         cleanup: Option<mir::BasicBlock>,
         panic_place: NodeRef,
     ) -> Result<()> {
-        if let Some((destination_node, destination_block)) = destination {
-            let node = place_to_data_node(destination_node, &self.virt_memory).clone();
-            let block = block_to_start_place!(self, net, *destination_block);
-
-            let cleanup = match cleanup {
-                Some(block) => Some(block_to_start_place!(self, net, block)),
-                None => None,
-            };
-            let source = active_block!(self).end_place().clone();
-            let mut arg_nodes = Vec::new();
-            for operand in args {
-                arg_nodes.push(op_to_data_node(operand, &self.virt_memory));
-            }
+        if destination.is_some() {
             match intrinsic_name {
                 name if name.contains("std::ops::DerefMut::deref_mut")
                 | name.contains("std::convert::Into::into")
@@ -347,10 +836,7 @@ impl<'mir> Function<'mir> {
                 | name.contains("std::intrinsics::atomic_load_acq")
                 | name.contains("std::intrinsics::atomic_load_relaxed")
                 | name.contains("std::intrinsics::atomic_load")
-                | name.contains("std::intrinsics::transmute") =>
-                {
-                    generic_foreign(net, &arg_nodes, source, node, block, cleanup, name.into())?
-                }
+                | name.contains("std::intrinsics::transmute") => {}
                 name if name.contains("libc::unix::pthread_mutexattr_init")
                     | name.contains("libc::unix::pthread_mutex_init")
                     | name.contains("libc::unix::pthread_mutexattr_settype")
@@ -358,24 +844,15 @@ impl<'mir> Function<'mir> {
                     | name.contains("libc::unix::pthread_mutex_lock") =>
                 {
                     warn!("mutex intrinsic {}", name);
-                    generic_foreign(net, &arg_nodes, source, node, block, cleanup, name.into())?
                 }
                 _ => {
                     warn!(
                         "unchecked intrinsic: {} args:{:?} dest:{:?}, cleanup:{:?}",
                         intrinsic_name, args, destination, cleanup
                     );
-                    generic_foreign(
-                        net,
-                        &arg_nodes,
-                        source,
-                        node,
-                        block,
-                        cleanup,
-                        intrinsic_name.into(),
-                    )?
                 }
             }
+            self.opaque_call(net, intrinsic_name, args, destination, cleanup)?
         } else {
             // diverging function (destination = none)
             match intrinsic_name {
@@ -385,24 +862,84 @@ impl<'mir> Function<'mir> {
                 {
                     self.handle_panic(net, panic_place)?
                 }
-                _ => panic!("unhandled diverging foreign: {}", intrinsic_name),
+                _ => {
+                    warn!("unchecked diverging intrinsic: {}", intrinsic_name);
+                    self.opaque_call(net, intrinsic_name, args, destination, cleanup)?
+                }
             }
         }
         Ok(())
     }
 
-    pub fn handle_panic(&mut self, net: &mut PetriNet, panic_place: NodeRef) -> Result<()> {
+    /// Models a call whose body is not translated as one atomic transition.
+    /// It reads and writes the arguments and the destination local and continues in the
+    /// destination block (with an extra transition into the cleanup block).
+    /// A diverging call (no destination) ends in a transition without successor.
+    pub fn opaque_call(
+        &mut self,
+        net: &mut Net,
+        name: &str,
+        args: &Vec<mir::Operand<'_>>,
+        destination: &Option<(mir::Place<'_>, mir::BasicBlock)>,
+        cleanup: Option<mir::BasicBlock>,
+    ) -> Result<()> {
+        let source = active_block!(self).end_place().clone();
+        match destination {
+            Some((destination_node, destination_block)) => {
+                let node = place_to_data_node(destination_node, &self.virt_memory)?;
+                let block = block_to_start_place!(self, net, *destination_block);
+                let cleanup = match cleanup {
+                    Some(block) => Some(block_to_start_place!(self, net, block)),
+                    None => None,
+                };
+                let mut arg_nodes = Vec::new();
+                for operand in args {
+                    arg_nodes.push(op_to_data_node(operand, &self.virt_memory)?);
+                }
+                let (t, t_unwind) = generic_foreign(net, &arg_nodes, source, node, block, cleanup)?;
+                self.name_transition(net, t, name.into())?;
+                if let Some(t_unwind) = t_unwind {
+                    self.name_transition(net, t_unwind, format!("unwind_{}", name))?;
+                }
+                Ok(())
+            }
+            None => {
+                let t = net.add_transition();
+                self.name_transition(net, t, format!("diverging {}", name))?;
+                net.add_arc(source, t)?;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn handle_panic(&mut self, net: &mut Net, panic_place: NodeRef) -> Result<()> {
         let source = active_block!(self).end_place().clone();
         let t = net.add_transition();
-        t.name(net, "panic".into())?;
+        self.name_transition(net, t, "panic".into())?;
         net.add_arc(source, t)?;
         net.add_arc(t, panic_place)?;
         Ok(())
     }
 
+    /// Models a call that ends the whole program (e.g. `std::process::exit`)
+    /// by moving the token into the given global place.
+    pub fn terminate(
+        &mut self,
+        net: &mut Net,
+        name: &str,
+        terminated_place: NodeRef,
+    ) -> Result<()> {
+        let source = active_block!(self).end_place().clone();
+        let t = net.add_transition();
+        self.name_transition(net, t, name.into())?;
+        net.add_arc(source, t)?;
+        net.add_arc(t, terminated_place)?;
+        Ok(())
+    }
+
     pub fn activate_block<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         block: mir::BasicBlock,
     ) -> Result<()> {
         match self.basic_blocks.get(&block) {
@@ -412,9 +949,120 @@ impl<'mir> Function<'mir> {
             }
         };
         self.active_block = Some(block);
+        // the values can differ on the paths into the block
+        self.known_values.clear();
+        self.discriminants.clear();
         Ok(())
     }
 
+    /// The place that is marked when the function is entered
+    pub fn start_place(&self) -> NodeRef {
+        self.start_place
+    }
+
+    /// The place that is marked when the function returns normally
+    pub fn return_place(&self) -> NodeRef {
+        self.return_flow
+    }
+
+    /// The place that is marked when the function unwinds
+    pub fn unwind_place(&self) -> NodeRef {
+        self.unwind_flow
+    }
+
+    /// The start and end place of every basic block that was added so far
+    pub fn block_places(&self) -> Vec<(mir::BasicBlock, NodeRef, NodeRef)> {
+        sorted_block_places(&self.basic_blocks)
+    }
+
+    /// The place of every local (the live place for locals with storage)
+    pub fn local_places(&self) -> Vec<(mir::Local, NodeRef)> {
+        self.virt_memory.local_places()
+    }
+
+    /// A readable listing of the places of the function to diagnose mis-wired arcs
+    pub fn dump(&self) -> String {
+        let mut dump = format!(
+            "{}\n  start: {:?}\n  return: {:?}\n  unwind: {:?}\n",
+            self.name, self.start_place, self.return_flow, self.unwind_flow
+        );
+        for (index, start, end) in self.block_places() {
+            dump.push_str(&format!("  {:?}: {:?} -> {:?}\n", index, start, end));
+        }
+        for (index, place) in self.local_places() {
+            dump.push_str(&format!("  {:?}: {:?}\n", index, place));
+        }
+        dump
+    }
+
+    /// Annotates the transitions that are added from now on with the given source location
+    pub fn set_source_location(&mut self, location: Option<String>) {
+        self.source_location = location;
+    }
+
+    // the transition name with the current source location (if any)
+    fn label(&self, name: String) -> String {
+        match &self.source_location {
+            Some(location) => format!("{} @ {}", name, location),
+            None => name,
+        }
+    }
+
+    /// Records the origin of the transitions that are added from now on (see `NodeSource`)
+    pub fn record_nodes(&mut self) {
+        self.nodes = Some(Vec::new());
+    }
+
+    /// The span of the statement or terminator that is translated next (for the node map)
+    pub fn set_span(&mut self, span: String) {
+        self.span = Some(span);
+    }
+
+    /// The recorded origins of the transitions of this function
+    pub fn take_nodes(&mut self) -> Vec<NodeSource> {
+        self.nodes.take().unwrap_or_default()
+    }
+
+    /// Names a transition with the current source location (if transitions are annotated)
+    /// and records its origin for the node map (if it is recorded).
+    /// Every transition that is added while the function is translated is named this way.
+    pub fn name_transition(&mut self, net: &mut Net, t: NodeRef, name: String) -> Result<()> {
+        net.name(t, self.label(name.clone()))?;
+        self.record(t, name);
+        Ok(())
+    }
+
+    /// Like `name_transition` for a transition that was already named
+    /// (e.g. the drop transition of a drop with further effects).
+    pub fn rename_transition(&mut self, net: &mut Net, t: NodeRef, name: String) -> Result<()> {
+        net.name(t, self.label(name.clone()))?;
+        let id = node_id(t);
+        let recorded = self
+            .nodes
+            .iter_mut()
+            .flatten()
+            .rev()
+            .find(|source| source.node == id);
+        match recorded {
+            Some(source) => source.name = name,
+            None => self.record(t, name),
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, node: NodeRef, name: String) {
+        let source = NodeSource {
+            node: node_id(node),
+            name,
+            function: self.name.clone(),
+            block: self.active_block.map(|block| block.index()),
+            span: self.span.clone(),
+        };
+        if let Some(nodes) = &mut self.nodes {
+            nodes.push(source);
+        }
+    }
+
     pub fn function_call_start_place(&self) -> Result<NodeRef> {
         let block = active_block!(self);
         Ok(block.end_place())
@@ -430,29 +1078,34 @@ impl<'mir> Function<'mir> {
 
     fn add_basic_block<'net>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         block: mir::BasicBlock,
     ) -> Result<&BasicBlock> {
         let start_place = if self.basic_blocks.is_empty() {
             self.start_place
         } else {
             let place = net.add_place();
-            place.name(net, format!("bb {}", block.index()))?;
+            net.name(place, format!("bb {}", block.index()))?;
             place
         };
-        let bb = BasicBlock::new(net, start_place)?;
+        let bb = match self.granularity {
+            Granularity::Statement => BasicBlock::new(net, start_place)?,
+            Granularity::Block | Granularity::Sync => BasicBlock::new_coarse(start_place),
+        };
+        if self.basic_blocks.insert(block, bb).is_some() {
+            return Err(TranslationError::UnexpectedMir(format!(
+                "{:?} was added twice",
+                block
+            )));
+        }
         self.basic_blocks
-            .insert(block, bb)
-            .expect_none("this should not happen");
-        Ok(self
-            .basic_blocks
             .get(&block)
-            .expect("this also should not happen"))
+            .ok_or_else(|| TranslationError::UnexpectedMir(format!("{:?} was not added", block)))
     }
 
     pub fn get_basic_block_start(
         &mut self,
-        net: &mut PetriNet,
+        net: &mut Net,
         block: mir::BasicBlock,
     ) -> Result<NodeRef> {
         Ok(block_to_start_place!(self, net, block))
@@ -460,7 +1113,7 @@ impl<'mir> Function<'mir> {
 
     fn add_locals<'net, 'tcx>(
         &mut self,
-        net: &'net mut PetriNet,
+        net: &'net mut Net,
         locals: &IndexVec<mir::Local, mir::LocalDecl<'tcx>>,
         known_locals: Vec<Local>,
         mutex_list: &mut MutexList,
@@ -470,9 +1123,16 @@ impl<'mir> Function<'mir> {
         // mir_local: mir::Local => index for local decls in mir data structure
         // decl: mir::LocalDecl => data of a local in mir data structure
         // local: crate:: .. ::Local => petri net representation of a local
+        // the caller and the callee share the places of the arguments and the return value,
+        // so no transition is needed to pass them
+        // (later calls of a shared subnet pass theirs, see `Translator::pass_locals`)
+        let arg_count = self.mir_body.arg_count;
         for (mir_local, decl) in locals.iter_enumerated() {
             let name = format!("{}_{}: {}", self.name, mir_local.index(), decl.ty);
-            let local = if let Some(local) = known_locals.get(mir_local.index()) {
+            let known = known_locals
+                .get(mir_local.index())
+                .filter(|_| mir_local.index() <= arg_count);
+            let local = if let Some(local) = known {
                 *local
             } else {
                 Local::new(net, &name)?
@@ -512,7 +1172,7 @@ impl<'mir> Function<'mir> {
         }
     }
 
-    pub fn op_to_local(&self, operand: &mir::Operand<'_>) -> Local {
+    pub fn op_to_local(&self, operand: &mir::Operand<'_>) -> Result<Local> {
         match operand {
             mir::Operand::Copy(place) | mir::Operand::Move(place) => self.place_to_local(place),
             mir::Operand::Constant(_) => {
@@ -520,60 +1180,175 @@ impl<'mir> Function<'mir> {
                     Data::Constant(constant) => *constant,
                     _ => panic!("Non constant stored in constant space"),
                 };
-                Local::new_constant(constant)
+                Ok(Local::new_constant(constant))
             }
         }
     }
-    pub fn place_to_local(&self, place: &mir::Place<'_>) -> Local {
-        match place.local_or_deref_local() {
-            Some(local) => *self.get_local(&local).expect("local not found"),
+    pub fn place_to_local(&self, place: &mir::Place<'_>) -> Result<Local> {
+        Ok(match place.local_or_deref_local() {
+            Some(local) => *self.virt_memory.find_local(&local)?,
             None => match &place.base {
-                mir::PlaceBase::Local(local) => *self.get_local(local).expect("local not found"),
+                mir::PlaceBase::Local(local) => *self.virt_memory.find_local(local)?,
                 mir::PlaceBase::Static(statik) => match statik.kind {
-                    mir::StaticKind::Static => panic!("staticKind::Static -> cannot convert"),
-                    mir::StaticKind::Promoted(promoted, _) => self
-                        .get_promoted(&promoted)
-                        .expect("promoted statik not found"),
+                    mir::StaticKind::Static => match self.virt_memory.get_global(&statik.def_id) {
+                        Some(global) => Local::new_constant(global),
+                        None => {
+                            warn!("static {:?} is treated as constant", statik.def_id);
+                            Local::new_constant(self.virt_memory.get_constant())
+                        }
+                    },
+                    mir::StaticKind::Promoted(promoted, _) => {
+                        Local::new_constant(self.virt_memory.find_static(&promoted)?)
+                    }
                 },
             },
+        })
+    }
+}
+
+// the start and end place of the blocks in the order of their index
+fn sorted_block_places(
+    blocks: &HashMap<mir::BasicBlock, BasicBlock>,
+) -> Vec<(mir::BasicBlock, NodeRef, NodeRef)> {
+    let mut places: Vec<_> = blocks
+        .iter()
+        .map(|(index, block)| (*index, block.start_place(), block.end_place()))
+        .collect();
+    places.sort_by_key(|(index, _, _)| *index);
+    places
+}
+
+/// Detects reborrows like `_2 = &mut (*_1)` which are introduced by the compiler for
+/// two-phase borrows and method call arguments.
+/// Returns the temporary and the local it reborrows.
+fn reborrow(place: &mir::Place<'_>, rvalue: &mir::Rvalue<'_>) -> Option<(mir::Local, mir::Local)> {
+    match (&place.base, rvalue) {
+        (mir::PlaceBase::Local(alias), mir::Rvalue::Ref(_, _, borrowed))
+            if place.projection.is_empty() =>
+        {
+            match (&borrowed.base, &borrowed.projection[..]) {
+                (mir::PlaceBase::Local(local), [mir::ProjectionElem::Deref]) => {
+                    Some((*alias, *local))
+                }
+                _ => None,
+            }
         }
+        _ => None,
     }
 }
 
-pub(crate) fn op_to_data_node(operand: &mir::Operand<'_>, memory: &VirtualMemory) -> NodeRef {
+/// The data nodes of the locals that are used as index in the projection of a place.
+/// Accessing `a[i]` reads `i` as well.
+pub(crate) fn index_data_nodes(
+    place: &mir::Place<'_>,
+    memory: &VirtualMemory,
+) -> Result<Vec<NodeRef>> {
+    place
+        .projection
+        .iter()
+        .filter_map(|elem| match elem {
+            mir::ProjectionElem::Index(local) => {
+                Some(memory.find_local(local).map(|local| local.live_place))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn op_to_data_node(
+    operand: &mir::Operand<'_>,
+    memory: &VirtualMemory,
+) -> Result<NodeRef> {
     match operand {
         mir::Operand::Copy(place) | mir::Operand::Move(place) => place_to_data_node(place, memory),
         // Constants are always valid reads
         // until using a high level petri net the value is not important and can be ignored
         // Constants can be seen as one petri net place that is accessed
-        mir::Operand::Constant(_) => memory.get_constant(),
+        mir::Operand::Constant(_) => Ok(memory.get_constant()),
     }
 }
 
-pub(crate) fn place_to_data_node(place: &mir::Place<'_>, memory: &VirtualMemory) -> NodeRef {
+pub(crate) fn place_to_data_node(
+    place: &mir::Place<'_>,
+    memory: &VirtualMemory,
+) -> Result<NodeRef> {
     let local = place.local_or_deref_local();
-    match local {
-        Some(local) => {
-            memory
-                .get_local(&local)
-                .expect("local not found")
-                .live_place
-        }
+    Ok(match local {
+        Some(local) => memory.find_local(&local)?.live_place,
         //FIXME: is it valid to just use the outermost local if nothing better was found?
         // maybe this functions helps?
         // https://doc.rust-lang.org/nightly/nightly-rustc/rustc/ty/context/struct.TyCtxt.html#method.intern_place_elems
         // https://doc.rust-lang.org/nightly/nightly-rustc/rustc/ty/context/struct.TyCtxt.html#method.mk_place_elems
         None => match &place.base {
-            mir::PlaceBase::Local(local) => {
-                memory.get_local(local).expect("local not found").live_place
-            }
+            mir::PlaceBase::Local(local) => memory.find_local(local)?.live_place,
             // https://doc.rust-lang.org/nightly/nightly-rustc/rustc/ty/context/struct.TyCtxt.html#method.promoted_mir
             mir::PlaceBase::Static(statik) => match statik.kind {
-                mir::StaticKind::Static => panic!("staticKind::Static -> cannot convert"),
-                mir::StaticKind::Promoted(promoted, _) => memory
-                    .get_static(&promoted)
-                    .expect("promoted statik not found"),
+                mir::StaticKind::Static => match memory.get_global(&statik.def_id) {
+                    Some(global) => global,
+                    None => {
+                        warn!("static {:?} is treated as constant", statik.def_id);
+                        memory.get_constant()
+                    }
+                },
+                mir::StaticKind::Promoted(promoted, _) => memory.find_static(&promoted)?,
             },
         },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sorted_block_places, Data, Local, VirtualMemory};
+    use crate::petri_net::basic_block::BasicBlock;
+    use crate::petri_net::net::Net;
+    use rustc::mir;
+    use rustc_index::vec::Idx;
+    use std::collections::HashMap;
+
+    #[test]
+    fn block_places_are_ordered() {
+        let mut net = Net::new();
+        let first = net.add_place();
+        let second = net.add_place();
+        let third = net.add_place();
+        // a block with statement transitions has an end place of its own
+        let statements = BasicBlock::new(&mut net, third).unwrap();
+        let end = statements.end_place();
+        let mut blocks = HashMap::new();
+        blocks.insert(mir::BasicBlock::new(2), statements);
+        blocks.insert(mir::BasicBlock::new(1), BasicBlock::new_coarse(second));
+        blocks.insert(mir::BasicBlock::new(0), BasicBlock::new_coarse(first));
+        assert_eq!(
+            sorted_block_places(&blocks),
+            vec![
+                (mir::BasicBlock::new(0), first, first),
+                (mir::BasicBlock::new(1), second, second),
+                (mir::BasicBlock::new(2), third, end),
+            ]
+        );
+    }
+
+    #[test]
+    fn local_places_are_live_places() {
+        let mut net = Net::new();
+        let local = Local::new(&mut net, "_1").unwrap();
+        let constant = net.add_place();
+        let mut locals = HashMap::new();
+        locals.insert(mir::Local::new(1), Data::Local(local));
+        locals.insert(mir::Local::new(0), Data::Constant(constant));
+        let memory = VirtualMemory {
+            locals,
+            aliases: HashMap::new(),
+            statics: HashMap::new(),
+            globals: HashMap::new(),
+            constants: Data::Constant(constant),
+        };
+        assert_eq!(
+            memory.local_places(),
+            vec![
+                (mir::Local::new(0), constant),
+                (mir::Local::new(1), local.live_place)
+            ]
+        );
     }
 }
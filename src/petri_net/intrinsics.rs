@@ -1,26 +1,29 @@
-use petri_to_star::{NodeRef, PetriNet, Result};
+use crate::error::Result;
+use crate::petri_net::net::Net;
+use petri_to_star::NodeRef;
 
 pub(crate) fn generic_foreign(
-    net: &mut PetriNet,
+    net: &mut Net,
     arg_nodes: &Vec<NodeRef>,
     source_node: NodeRef,
     destination_node: NodeRef, // local var that holds the return value
     destination_block_start: NodeRef, // start of bb to continue
     cleanup_node: Option<NodeRef>, // start of fail case bb
-    name: String,
-) -> Result<()> {
+) -> Result<(NodeRef, Option<NodeRef>)> {
     //flow
     let t = net.add_transition();
-    t.name(net, name.clone())?;
     net.add_arc(source_node, t)?;
     net.add_arc(t, destination_block_start)?;
     // extra unwind transition
-    if let Some(node) = cleanup_node {
-        let t_unwind = net.add_transition();
-        t_unwind.name(net, format!("unwind_{}", name))?;
-        net.add_arc(source_node, t_unwind)?;
-        net.add_arc(t_unwind, node)?;
-    }
+    let t_unwind = match cleanup_node {
+        Some(node) => {
+            let t_unwind = net.add_transition();
+            net.add_arc(source_node, t_unwind)?;
+            net.add_arc(t_unwind, node)?;
+            Some(t_unwind)
+        }
+        None => None,
+    };
     //vars
     net.add_arc(destination_node, t)?;
     net.add_arc(t, destination_node)?;
@@ -28,5 +31,6 @@ pub(crate) fn generic_foreign(
         net.add_arc(*node, t)?;
         net.add_arc(t, *node)?;
     }
-    Ok(())
+    // the transitions are named by the caller
+    Ok((t, t_unwind))
 }
@@ -1,6 +1,7 @@
 pub mod basic_block;
 pub mod function;
 mod intrinsics;
+pub mod net;
 mod tests;
 mod trait_impls;
 pub mod unique_functions;
@@ -0,0 +1,181 @@
+// The net that is built by the translation.
+// `petri_to_star` cannot be inspected, so every node and arc is recorded as it is added,
+// together with the function that was translated at the time. The statistics and the
+// exports that need more than the writers of `petri_to_star` read this record.
+
+use crate::watchdog::Progress;
+use petri_to_star::{NodeRef, PetriNet, PlaceRef, Result};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Place,
+    Transition,
+}
+
+/// A recorded node of the net
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub node: NodeRef,
+    pub kind: NodeKind,
+    pub name: Option<String>,
+    /// the initial marking of a place
+    pub marking: usize,
+    /// the function that was translated when the node was added
+    /// (`None` for the places of the program and of shared resources)
+    pub function: Option<String>,
+}
+
+/// A recorded arc of the net
+#[derive(Debug, Clone)]
+pub struct NetArc {
+    pub source: NodeRef,
+    pub target: NodeRef,
+    /// the function that was translated when the arc was added
+    pub function: Option<String>,
+}
+
+pub struct Net {
+    net: PetriNet,
+    nodes: Vec<Node>,
+    // the position of the node in `nodes`
+    index: HashMap<NodeRef, usize>,
+    arcs: Vec<NetArc>,
+    // the functions that are translated at the moment, the innermost last
+    functions: Vec<String>,
+    start: Option<NodeRef>,
+    end: Option<NodeRef>,
+    // every new node is progress of the translation
+    progress: Option<Progress>,
+}
+
+impl Net {
+    pub fn new() -> Self {
+        Net {
+            net: PetriNet::new(),
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            arcs: Vec::new(),
+            functions: Vec::new(),
+            start: None,
+            end: None,
+            progress: None,
+        }
+    }
+
+    /// Reports every node that is added from now on to the watchdog
+    pub(crate) fn report_progress(&mut self, progress: Progress) {
+        self.progress = Some(progress);
+    }
+
+    pub fn add_place(&mut self) -> NodeRef {
+        let place = self.net.add_place();
+        self.record(place, NodeKind::Place);
+        place
+    }
+
+    pub fn add_transition(&mut self) -> NodeRef {
+        let transition = self.net.add_transition();
+        self.record(transition, NodeKind::Transition);
+        transition
+    }
+
+    pub fn add_arc(&mut self, source: NodeRef, target: NodeRef) -> Result<()> {
+        self.net.add_arc(source, target)?;
+        self.arcs.push(NetArc {
+            source,
+            target,
+            function: self.functions.last().cloned(),
+        });
+        Ok(())
+    }
+
+    pub fn name(&mut self, node: NodeRef, name: String) -> Result<()> {
+        node.name(&mut self.net, name.clone())?;
+        if let Some(index) = self.index.get(&node) {
+            self.nodes[*index].name = Some(name);
+        }
+        Ok(())
+    }
+
+    /// Sets the initial marking of the place
+    pub fn mark(&mut self, place: NodeRef, tokens: usize) -> Result<()> {
+        PlaceRef::try_from(place)?.marking(&mut self.net, tokens)?;
+        if let Some(index) = self.index.get(&place) {
+            self.nodes[*index].marking = tokens;
+        }
+        Ok(())
+    }
+
+    /// Attributes the nodes that are added from now on to the function (until `leave`)
+    pub fn enter(&mut self, function: String) {
+        self.functions.push(function);
+    }
+
+    pub fn leave(&mut self) {
+        self.functions.pop();
+    }
+
+    /// Remembers the place of the initial marking and the place that is marked when the
+    /// program returns normally, so the exports can point tools to them
+    pub fn set_program_places(&mut self, start: NodeRef, end: NodeRef) {
+        self.start = Some(start);
+        self.end = Some(end);
+    }
+
+    pub fn start_place(&self) -> Option<NodeRef> {
+        self.start
+    }
+
+    pub fn end_place(&self) -> Option<NodeRef> {
+        self.end
+    }
+
+    /// The nodes in the order in which they were added
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn node(&self, node: NodeRef) -> Option<&Node> {
+        self.index.get(&node).map(|index| &self.nodes[*index])
+    }
+
+    /// The arcs in the order in which they were added
+    pub fn arcs(&self) -> &[NetArc] {
+        &self.arcs
+    }
+
+    pub fn petri_net(&self) -> &PetriNet {
+        &self.net
+    }
+
+    pub fn into_petri_net(self) -> PetriNet {
+        self.net
+    }
+
+    fn record(&mut self, node: NodeRef, kind: NodeKind) {
+        if let Some(progress) = &self.progress {
+            progress.report();
+        }
+        self.index.insert(node, self.nodes.len());
+        self.nodes.push(Node {
+            node,
+            kind,
+            name: None,
+            marking: 0,
+            function: self.functions.last().cloned(),
+        });
+    }
+}
+
+/// The id of the node in the exports and in the node map
+pub fn node_id(node: NodeRef) -> String {
+    format!("{:?}", node)
+}
+
+impl Default for Net {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,9 +1,9 @@
 use log::warn;
-use petri_to_star::{NodeRef, PetriNet, PlaceRef, Result};
+use petri_to_star::{NodeRef, Result};
 use std::collections::{HashMap, HashSet};
-use std::convert::TryFrom;
 
 use super::function::{Function, Local};
+use super::net::Net;
 
 #[derive(Debug)]
 pub struct MutexList {
@@ -23,6 +23,8 @@ pub struct Mutex {
     unlocked: NodeRef,
     locked: NodeRef,
     dead: NodeRef,
+    // marked by every guard that is dropped while unwinding
+    poisoned: NodeRef,
 }
 
 impl MutexRef {
@@ -41,6 +43,9 @@ impl MutexRef {
     pub fn dead(&self, list: &MutexList) -> NodeRef {
         list.list.get(self.index).expect("mutex not found").dead
     }
+    pub fn poisoned(&self, list: &MutexList) -> NodeRef {
+        list.list.get(self.index).expect("mutex not found").poisoned
+    }
 }
 
 impl MutexList {
@@ -52,34 +57,61 @@ impl MutexList {
         }
     }
 
-    pub fn get_linked(&mut self, local: Local) -> Option<&MutexRef> {
+    pub fn get_linked(&self, local: Local) -> Option<&MutexRef> {
         self.links.get(&local)
     }
 
-    pub fn add(&mut self, net: &mut PetriNet) -> Result<MutexRef> {
+    pub fn add(&mut self, net: &mut Net) -> Result<MutexRef> {
         let index = self.list.len();
         let uninitialized = net.add_place();
-        uninitialized.name(net, format!("Mutex_{} uninitialized", index))?;
-        PlaceRef::try_from(uninitialized)?.marking(net, 1)?;
+        net.name(uninitialized, format!("Mutex_{} uninitialized", index))?;
+        net.mark(uninitialized, 1)?;
         let locked = net.add_place();
-        locked.name(net, format!("Mutex_{} locked", index))?;
+        net.name(locked, format!("Mutex_{} locked", index))?;
         let unlocked = net.add_place();
-        unlocked.name(net, format!("Mutex_{} unlocked", index))?;
+        net.name(unlocked, format!("Mutex_{} unlocked", index))?;
         let dead = net.add_place();
-        dead.name(net, format!("Mutex_{} dead", index))?;
+        net.name(dead, format!("Mutex_{} dead", index))?;
+        let poisoned = net.add_place();
+        net.name(poisoned, format!("Mutex_{} poisoned", index))?;
         self.list.push(Mutex {
             uninitialized,
             unlocked,
             locked,
             dead,
+            poisoned,
         });
         Ok(MutexRef { index })
     }
 
+    /// A mutex that is already initialized and unlocked.
+    /// Used if the creation of the mutex is not part of the translation.
+    pub fn add_unlocked(&mut self, net: &mut Net) -> Result<MutexRef> {
+        let mutex = self.add(net)?;
+        net.mark(mutex.uninitialized(self), 0)?;
+        net.mark(mutex.unlocked(self), 1)?;
+        Ok(mutex)
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// All mutexes in the order in which they were added
+    pub fn mutexes(&self) -> Vec<MutexRef> {
+        (0..self.list.len())
+            .map(|index| MutexRef { index })
+            .collect()
+    }
+
     pub fn add_guard(&mut self, guard: Local, mutex: MutexRef) {
         self.guards.insert(guard, mutex);
     }
 
+    pub fn get_guard(&self, local: Local) -> Option<&MutexRef> {
+        self.guards.get(&local)
+    }
+
     pub fn is_linked(&self, local: Local) -> Option<&MutexRef> {
         self.links.get(&local)
     }
@@ -95,3 +127,546 @@ impl MutexList {
         };
     }
 }
+
+#[derive(Debug, Clone, Copy)]
+pub enum ChannelFunction {
+    Channel,
+    SyncChannel,
+    Send,
+    Recv,
+    // another producer of the same channel
+    Clone,
+}
+
+#[derive(Debug)]
+pub struct ChannelList {
+    list: Vec<Channel>,
+    links: HashMap<Local, ChannelRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct ChannelRef {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct Channel {
+    // one token per message in the channel
+    buffer: NodeRef,
+    // one token per free slot of a bounded channel
+    free: Option<NodeRef>,
+    // one token per living `Sender` (or `SyncSender`)
+    senders: NodeRef,
+    // marked until the last sender is dropped
+    connected: NodeRef,
+    // marked after the last sender was dropped, `recv` fails from then on
+    disconnected: NodeRef,
+}
+
+impl ChannelRef {
+    pub fn buffer(&self, list: &ChannelList) -> NodeRef {
+        list.list.get(self.index).expect("channel not found").buffer
+    }
+    pub fn free(&self, list: &ChannelList) -> Option<NodeRef> {
+        list.list.get(self.index).expect("channel not found").free
+    }
+    pub fn senders(&self, list: &ChannelList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("channel not found")
+            .senders
+    }
+    pub fn connected(&self, list: &ChannelList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("channel not found")
+            .connected
+    }
+    pub fn disconnected(&self, list: &ChannelList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("channel not found")
+            .disconnected
+    }
+}
+
+impl ChannelList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&ChannelRef> {
+        self.links.get(&local)
+    }
+
+    /// A new channel without senders, the creating transition adds the first one.
+    pub fn add(&mut self, net: &mut Net, capacity: Option<u64>) -> Result<ChannelRef> {
+        let index = self.list.len();
+        let buffer = net.add_place();
+        net.name(buffer, format!("Channel_{} buffer", index))?;
+        let free = match capacity {
+            Some(capacity) => {
+                let free = net.add_place();
+                net.name(free, format!("Channel_{} free", index))?;
+                net.mark(free, capacity as usize)?;
+                Some(free)
+            }
+            None => None,
+        };
+        let senders = net.add_place();
+        net.name(senders, format!("Channel_{} senders", index))?;
+        let connected = net.add_place();
+        net.name(connected, format!("Channel_{} connected", index))?;
+        net.mark(connected, 1)?;
+        let disconnected = net.add_place();
+        net.name(disconnected, format!("Channel_{} disconnected", index))?;
+        self.list.push(Channel {
+            buffer,
+            free,
+            senders,
+            connected,
+            disconnected,
+        });
+        Ok(ChannelRef { index })
+    }
+
+    /// An unbounded channel with one sender.
+    /// Used if the creation of the channel is not part of the translation.
+    pub fn add_referenced(&mut self, net: &mut Net) -> Result<ChannelRef> {
+        let channel = self.add(net, None)?;
+        net.mark(channel.senders(self), 1)?;
+        Ok(channel)
+    }
+
+    pub fn link(&mut self, local: Local, channel: ChannelRef) {
+        match self.links.insert(local, channel) {
+            None => {}
+            Some(old_channel) => {
+                if old_channel != channel {
+                    warn!("Local '{:?}' was already linked to channel '{:?}'. The old value will be overridden with channel '{:?}'", local, old_channel, channel)
+                }
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArcFunction {
+    New,
+    Clone,
+}
+
+#[derive(Debug)]
+pub struct ArcList {
+    list: Vec<ArcCounter>,
+    links: HashMap<Local, ArcRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct ArcRef {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct ArcCounter {
+    // one token per living reference
+    count: NodeRef,
+    // marked until the inner value is dropped
+    alive: NodeRef,
+}
+
+impl ArcRef {
+    pub fn count(&self, list: &ArcList) -> NodeRef {
+        list.list.get(self.index).expect("arc not found").count
+    }
+    pub fn alive(&self, list: &ArcList) -> NodeRef {
+        list.list.get(self.index).expect("arc not found").alive
+    }
+}
+
+impl ArcList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&ArcRef> {
+        self.links.get(&local)
+    }
+
+    /// A new counter without references, the creating transition adds the first one.
+    pub fn add(&mut self, net: &mut Net) -> Result<ArcRef> {
+        let index = self.list.len();
+        let count = net.add_place();
+        net.name(count, format!("Arc_{} count", index))?;
+        let alive = net.add_place();
+        net.name(alive, format!("Arc_{} alive", index))?;
+        self.list.push(ArcCounter { count, alive });
+        Ok(ArcRef { index })
+    }
+
+    /// A counter with one reference.
+    /// Used if the creation of the `Arc` is not part of the translation.
+    pub fn add_referenced(&mut self, net: &mut Net) -> Result<ArcRef> {
+        let arc = self.add(net)?;
+        net.mark(arc.count(self), 1)?;
+        net.mark(arc.alive(self), 1)?;
+        Ok(arc)
+    }
+
+    pub fn link(&mut self, local: Local, arc: ArcRef) {
+        match self.links.insert(local, arc) {
+            None => {}
+            Some(old_arc) => {
+                if old_arc != arc {
+                    warn!("Local '{:?}' was already linked to arc '{:?}'. The old value will be overridden with arc '{:?}'", local, old_arc, arc)
+                }
+            }
+        };
+    }
+}
+
+/// Readers that can hold a `RwLock` at the same time if not configured otherwise
+pub const DEFAULT_READER_SLOTS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub enum RwLockFunction {
+    New,
+    Read,
+    Write,
+    TryRead,
+    TryWrite,
+}
+
+#[derive(Debug)]
+pub struct RwLockList {
+    list: Vec<RwLock>,
+    links: HashMap<Local, RwLockRef>,
+    // the lock of a guard and whether it is a write guard
+    guards: HashMap<Local, (RwLockRef, bool)>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct RwLockRef {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct RwLock {
+    // taken by a writer, readers test it
+    writer: NodeRef,
+    // one token per reader that can still enter, a writer takes all of them
+    slots: NodeRef,
+    // a dropped write guard gives the slots back one by one
+    releasing: NodeRef,
+    readers: usize,
+}
+
+impl RwLockRef {
+    pub fn writer(&self, list: &RwLockList) -> NodeRef {
+        list.list.get(self.index).expect("rwlock not found").writer
+    }
+    pub fn slots(&self, list: &RwLockList) -> NodeRef {
+        list.list.get(self.index).expect("rwlock not found").slots
+    }
+    pub fn releasing(&self, list: &RwLockList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("rwlock not found")
+            .releasing
+    }
+    pub fn readers(&self, list: &RwLockList) -> usize {
+        list.list.get(self.index).expect("rwlock not found").readers
+    }
+}
+
+impl RwLockList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+            guards: HashMap::new(),
+        }
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&RwLockRef> {
+        self.links.get(&local)
+    }
+
+    /// An unlocked `RwLock` for the given number of readers.
+    /// Arcs have no weights, so the release of a writer is a chain of transitions
+    /// that return one slot each and the writer token at last.
+    pub fn add(&mut self, net: &mut Net, readers: usize) -> Result<RwLockRef> {
+        // without slots a writer would never give the lock back
+        let readers = readers.max(1);
+        let index = self.list.len();
+        let writer = net.add_place();
+        net.name(writer, format!("RwLock_{} writer", index))?;
+        net.mark(writer, 1)?;
+        let slots = net.add_place();
+        net.name(slots, format!("RwLock_{} slots", index))?;
+        net.mark(slots, readers)?;
+        let releasing = net.add_place();
+        net.name(releasing, format!("RwLock_{} releasing", index))?;
+        let mut source = releasing;
+        for slot in 0..readers {
+            let t = net.add_transition();
+            net.name(t, format!("RwLock_{} release slot {}", index, slot))?;
+            net.add_arc(source, t)?;
+            net.add_arc(t, slots)?;
+            if slot + 1 == readers {
+                net.add_arc(t, writer)?;
+            } else {
+                source = net.add_place();
+                net.add_arc(t, source)?;
+            }
+        }
+        self.list.push(RwLock {
+            writer,
+            slots,
+            releasing,
+            readers,
+        });
+        Ok(RwLockRef { index })
+    }
+
+    pub fn add_guard(&mut self, guard: Local, lock: RwLockRef, write: bool) {
+        self.guards.insert(guard, (lock, write));
+    }
+
+    pub fn get_guard(&self, local: Local) -> Option<&(RwLockRef, bool)> {
+        self.guards.get(&local)
+    }
+
+    pub fn link(&mut self, local: Local, lock: RwLockRef) {
+        match self.links.insert(local, lock) {
+            None => {}
+            Some(old_lock) => {
+                if old_lock != lock {
+                    warn!("Local '{:?}' was already linked to rwlock '{:?}'. The old value will be overridden with rwlock '{:?}'", local, old_lock, lock)
+                }
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AtomicFunction {
+    New,
+    Load,
+    Store,
+    CompareExchange,
+    // swap and fetch_* read and write in one step
+    ReadModifyWrite,
+}
+
+/// Atomics have a place each that is marked while no operation accesses it.
+/// The net is not colored, so the place does not hold the value itself.
+#[derive(Debug)]
+pub struct AtomicList {
+    list: Vec<NodeRef>,
+    links: HashMap<Local, AtomicRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct AtomicRef {
+    index: usize,
+}
+
+impl AtomicRef {
+    pub fn value(&self, list: &AtomicList) -> NodeRef {
+        *list.list.get(self.index).expect("atomic not found")
+    }
+}
+
+impl AtomicList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&AtomicRef> {
+        self.links.get(&local)
+    }
+
+    pub fn add(&mut self, net: &mut Net) -> Result<AtomicRef> {
+        let index = self.list.len();
+        let value = net.add_place();
+        net.name(value, format!("Atomic_{} value", index))?;
+        net.mark(value, 1)?;
+        self.list.push(value);
+        Ok(AtomicRef { index })
+    }
+
+    pub fn link(&mut self, local: Local, atomic: AtomicRef) {
+        match self.links.insert(local, atomic) {
+            None => {}
+            Some(old_atomic) => {
+                if old_atomic != atomic {
+                    warn!("Local '{:?}' was already linked to atomic '{:?}'. The old value will be overridden with atomic '{:?}'", local, old_atomic, atomic)
+                }
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CondvarFunction {
+    New,
+    Wait,
+    NotifyOne,
+    NotifyAll,
+}
+
+#[derive(Debug)]
+pub struct CondvarList {
+    list: Vec<Condvar>,
+    links: HashMap<Local, CondvarRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct CondvarRef {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct Condvar {
+    // one token per waiting thread that was not notified yet
+    waiters: NodeRef,
+    // one token per notified thread that did not reacquire its mutex yet
+    notified: NodeRef,
+}
+
+impl CondvarRef {
+    pub fn waiters(&self, list: &CondvarList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("condvar not found")
+            .waiters
+    }
+    pub fn notified(&self, list: &CondvarList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("condvar not found")
+            .notified
+    }
+}
+
+impl CondvarList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&CondvarRef> {
+        self.links.get(&local)
+    }
+
+    pub fn add(&mut self, net: &mut Net) -> Result<CondvarRef> {
+        let index = self.list.len();
+        let waiters = net.add_place();
+        net.name(waiters, format!("Condvar_{} waiters", index))?;
+        let notified = net.add_place();
+        net.name(notified, format!("Condvar_{} notified", index))?;
+        self.list.push(Condvar { waiters, notified });
+        Ok(CondvarRef { index })
+    }
+
+    pub fn link(&mut self, local: Local, condvar: CondvarRef) {
+        match self.links.insert(local, condvar) {
+            None => {}
+            Some(old_condvar) => {
+                if old_condvar != condvar {
+                    warn!("Local '{:?}' was already linked to condvar '{:?}'. The old value will be overridden with condvar '{:?}'", local, old_condvar, condvar)
+                }
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OnceFunction {
+    New,
+    // `Once::call_once` and `OnceCell::get_or_init`
+    CallOnce,
+}
+
+#[derive(Debug)]
+pub struct OnceList {
+    list: Vec<Once>,
+    links: HashMap<Local, OnceRef>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct OnceRef {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct Once {
+    // taken by the caller that runs the initialization
+    uninitialized: NodeRef,
+    initialized: NodeRef,
+}
+
+impl OnceRef {
+    pub fn uninitialized(&self, list: &OnceList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("once not found")
+            .uninitialized
+    }
+    pub fn initialized(&self, list: &OnceList) -> NodeRef {
+        list.list
+            .get(self.index)
+            .expect("once not found")
+            .initialized
+    }
+}
+
+impl OnceList {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn get_linked(&self, local: Local) -> Option<&OnceRef> {
+        self.links.get(&local)
+    }
+
+    pub fn add(&mut self, net: &mut Net) -> Result<OnceRef> {
+        let index = self.list.len();
+        let uninitialized = net.add_place();
+        net.name(uninitialized, format!("Once_{} uninitialized", index))?;
+        net.mark(uninitialized, 1)?;
+        let initialized = net.add_place();
+        net.name(initialized, format!("Once_{} initialized", index))?;
+        self.list.push(Once {
+            uninitialized,
+            initialized,
+        });
+        Ok(OnceRef { index })
+    }
+
+    pub fn link(&mut self, local: Local, once: OnceRef) {
+        match self.links.insert(local, once) {
+            None => {}
+            Some(old_once) => {
+                if old_once != once {
+                    warn!("Local '{:?}' was already linked to once '{:?}'. The old value will be overridden with once '{:?}'", local, old_once, once)
+                }
+            }
+        };
+    }
+}
@@ -0,0 +1,123 @@
+// A line delimited JSON protocol on stdin/stdout for editor integration.
+// The compiler session stays alive between requests, so only the translation is repeated.
+//
+// request:  {"function": "path::to::function"}
+// response: {"function": "path::to::function", "ok": true, "mutexes": 1,
+//            "start": "crate start", "end": "crate end",
+//            "net": {...}, "issues": [{"kind": "poison", "place": "...", "name": "..."}],
+//            "lola": "..."}
+//       or: {"function": "path::to::function", "ok": false, "error": "..."}
+// `net` is the JSON export (see `json`) with the nodes, arcs and their ids and
+// `issues` are the places that show a deadlock or a poisoned mutex if they are reachable.
+
+use crate::error::Result;
+use crate::json::JsonNet;
+use crate::petri_net::net::node_id;
+use crate::translator::{Options, Translator};
+use rustc::ty::TyCtxt;
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+pub fn serve(tcx: TyCtxt<'_>, options: &Options) {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                error!("unable to read request: {}", err);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => match request["function"].as_str() {
+                Some(function) => translate(tcx, options, function),
+                None => json!({"ok": false, "error": "missing field 'function'"}),
+            },
+            Err(err) => json!({"ok": false, "error": format!("invalid request: {}", err)}),
+        };
+        let mut out = stdout.lock();
+        // without a reader there is nobody left to serve
+        if let Err(err) = writeln!(out, "{}", response).and_then(|_| out.flush()) {
+            error!("unable to write response: {}", err);
+            break;
+        }
+    }
+}
+
+fn translate(tcx: TyCtxt<'_>, options: &Options, function: &str) -> Value {
+    let def_id = match find_function(tcx, function) {
+        Some(def_id) => def_id,
+        None => {
+            return json!({"function": function, "ok": false, "error": "function not found"});
+        }
+    };
+    match translate_function(tcx, options, def_id) {
+        Ok(mut response) => {
+            response["function"] = json!(function);
+            response
+        }
+        Err(err) => json!({"function": function, "ok": false, "error": format!("{}", err)}),
+    }
+}
+
+fn translate_function(tcx: TyCtxt<'_>, options: &Options, def_id: DefId) -> Result<Value> {
+    let mut translator = Translator::new(
+        tcx,
+        tcx.crate_name(LOCAL_CRATE).to_string(),
+        None,
+        options.clone(),
+    )?;
+    let mut lola = Vec::new();
+    translator.petrify(def_id)?.to_lola(&mut lola)?;
+    let net = translator.net();
+    let issues: Vec<Value> = translator
+        .issue_places()
+        .into_iter()
+        .map(|(kind, place)| {
+            json!({
+                "kind": kind,
+                "place": node_id(place),
+                "name": net.node(place).and_then(|node| node.name.clone()),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "ok": true,
+        "mutexes": translator.mutex_count(),
+        "start": translator.start_place_name(),
+        "end": translator.end_place_name(),
+        "net": JsonNet::read(net, translator.node_map()),
+        "issues": issues,
+        "lola": String::from_utf8_lossy(&lola),
+    }))
+}
+
+/// Searches the local crate for a function with the given path
+pub fn find_function(tcx: TyCtxt<'_>, path: &str) -> Option<DefId> {
+    tcx.body_owners()
+        .find(|def_id| tcx.def_path_str(*def_id) == path)
+}
+
+/// All functions and methods of the local crate
+pub fn function_ids(tcx: TyCtxt<'_>) -> Vec<DefId> {
+    tcx.body_owners()
+        .filter(|def_id| match tcx.def_kind(*def_id) {
+            Some(DefKind::Fn) | Some(DefKind::Method) => true,
+            _ => false,
+        })
+        .collect()
+}
+
+/// The paths of all functions and methods of the local crate
+pub fn functions(tcx: TyCtxt<'_>) -> Vec<String> {
+    function_ids(tcx)
+        .into_iter()
+        .map(|def_id| tcx.def_path_str(def_id))
+        .collect()
+}
@@ -0,0 +1,87 @@
+// Size of the generated net.
+// The nodes and arcs are counted in the record of the net as they were added,
+// in total and for the function that was translated at the time.
+
+use crate::petri_net::net::{Net, NodeKind};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct NetStats {
+    pub places: usize,
+    pub transitions: usize,
+    pub arcs: usize,
+    /// function instances that got their own subnet
+    pub functions_translated: usize,
+    /// the nodes of every function instance, the subnet and the connections of its calls
+    /// (the places of the program and of shared resources belong to no function)
+    pub functions: BTreeMap<String, FunctionStats>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FunctionStats {
+    pub places: usize,
+    pub transitions: usize,
+    pub arcs: usize,
+}
+
+impl NetStats {
+    pub fn count(net: &Net, functions_translated: usize) -> Self {
+        let mut stats = NetStats {
+            functions_translated,
+            ..NetStats::default()
+        };
+        for node in net.nodes() {
+            let function = node
+                .function
+                .as_ref()
+                .map(|function| stats.functions.entry(function.clone()).or_default());
+            match node.kind {
+                NodeKind::Place => {
+                    stats.places += 1;
+                    if let Some(function) = function {
+                        function.places += 1;
+                    }
+                }
+                NodeKind::Transition => {
+                    stats.transitions += 1;
+                    if let Some(function) = function {
+                        function.transitions += 1;
+                    }
+                }
+            }
+        }
+        for arc in net.arcs() {
+            stats.arcs += 1;
+            if let Some(function) = &arc.function {
+                stats.functions.entry(function.clone()).or_default().arcs += 1;
+            }
+        }
+        stats
+    }
+}
+
+impl fmt::Display for NetStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} places, {} transitions, {} arcs, {} functions",
+            self.places, self.transitions, self.arcs, self.functions_translated
+        )?;
+        for (name, function) in &self.functions {
+            write!(f, "\n    {}: {}", name, function)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FunctionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} places, {} transitions, {} arcs",
+            self.places, self.transitions, self.arcs
+        )
+    }
+}
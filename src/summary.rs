@@ -0,0 +1,66 @@
+// User defined summaries of library functions.
+// A summary replaces the body of a function with one transition that consumes and
+// produces tokens of named resources, e.g.:
+//
+// [resources]
+// printer = 1 # initial tokens
+//
+// [[function]]
+// path = "mylib::Printer::acquire"
+// consume = ["printer"]
+//
+// [[function]]
+// path = "mylib::Printer::release"
+// produce = ["printer"]
+//
+// [[function]]
+// path = "tokio::spawn"
+// spawn = true # the future argument runs like a spawned thread
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Summaries {
+    #[serde(default)]
+    pub resources: HashMap<String, usize>,
+    #[serde(default, rename = "function")]
+    pub functions: Vec<Summary>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Summary {
+    pub path: String,
+    #[serde(default)]
+    pub consume: Vec<String>,
+    #[serde(default)]
+    pub produce: Vec<String>,
+    /// the function spawns its future (or closure) argument, e.g. an executor
+    #[serde(default)]
+    pub spawn: bool,
+}
+
+impl Summaries {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("unable to read {}: {}", path.display(), err))?;
+        let summaries: Summaries = toml::from_str(&content)
+            .map_err(|err| format!("invalid summaries in {}: {}", path.display(), err))?;
+        for summary in &summaries.functions {
+            for resource in summary.consume.iter().chain(summary.produce.iter()) {
+                if !summaries.resources.contains_key(resource) {
+                    return Err(format!(
+                        "unknown resource {} in the summary of {}",
+                        resource, summary.path
+                    ));
+                }
+            }
+        }
+        Ok(summaries)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Summary> {
+        self.functions.iter().find(|summary| summary.path == path)
+    }
+}
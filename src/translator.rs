@@ -1,14 +1,29 @@
-use crate::petri_net::function::{Data, Function, Local};
-use crate::petri_net::unique_functions::MutexList;
-use petri_to_star::{NodeRef, PetriNet, PlaceRef, Result};
+use crate::error::{Result, TranslationError};
+pub use crate::petri_net::function::Granularity;
+use crate::petri_net::function::{Data, Function, Local, NodeSource};
+use crate::petri_net::net::Net;
+use crate::petri_net::unique_functions::{
+    ArcFunction, ArcList, ArcRef, AtomicFunction, AtomicList, AtomicRef, ChannelFunction,
+    ChannelList, ChannelRef, CondvarFunction, CondvarList, CondvarRef, MutexList, MutexRef,
+    OnceFunction, OnceList, OnceRef, RwLockFunction, RwLockList, RwLockRef, DEFAULT_READER_SLOTS,
+};
+use crate::stats::NetStats;
+use crate::summary::Summaries;
+use crate::watchdog::Watchdog;
+use petri_to_star::{NodeRef, PetriNet};
 use rustc::mir::visit::Visitor;
 use rustc::mir::visit::*;
 use rustc::mir::{self, *};
+use rustc::ty::fold::TypeFoldable;
+use rustc::ty::layout::VariantIdx;
+use rustc::ty::subst::{InternalSubsts, Subst, SubstsRef};
 use rustc::ty::{self, Ty, TyCtxt};
 use rustc_hir::def_id::DefId;
 use rustc_mir::util::write_mir_pretty;
-use std::collections::HashSet;
-use std::convert::TryFrom;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
 
 struct CallStack<T> {
     stack: Vec<T>,
@@ -47,17 +62,122 @@ impl<T> CallStack<T> {
     pub fn is_empty(&self) -> bool {
         self.stack.is_empty()
     }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+}
+
+/// Places through which a translated function is entered and left.
+/// Later calls of the same function are connected to them instead of translating it again.
+#[derive(Clone)]
+struct Subnet {
+    entry: NodeRef,
+    exit: NodeRef,
+    unwind_exit: NodeRef,
+    // the locals of the first call that the subnet uses as `_1` to `_n` and `_0`
+    args: Vec<Local>,
+    data_return: Local,
+}
+
+/// Optional behavior of the translation
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// abort if the translator makes no progress in the given interval
+    pub watchdog: Option<Duration>,
+    /// how many transitions are used to model a basic block
+    pub granularity: Granularity,
+    /// do not model the failing paths of assertions
+    pub prune_panics: bool,
+    /// keep the failing paths of overflow checks even if panics are pruned
+    pub model_overflow: bool,
+    /// call every function whose address was taken for an unresolved function pointer
+    pub fn_pointer_candidates: bool,
+    /// append the source location to the transition names
+    pub annotate_source: bool,
+    /// abort the translation on constructs that cannot be modeled
+    pub strict: bool,
+    /// call every implementation of the local crate for a call through a trait object
+    pub resolve_dyn: bool,
+    /// paths of functions (or modules) that are modeled as a single opaque transition
+    pub atomic: Vec<String>,
+    /// module prefixes whose functions are not translated but modeled as opaque transitions
+    pub exclude: Vec<String>,
+    /// paths of functions (or modules) that are translated even if they match `atomic`,
+    /// `exclude` or are deeper than `max_depth`
+    pub force_inline: Vec<String>,
+    /// a file with summaries of functions (see `summary`)
+    pub summaries: Option<PathBuf>,
+    /// models Unreachable terminators as transitions into an "unreachable" sink place
+    /// instead of leaving their block as a dead end
+    pub unreachable_sink: bool,
+    /// records the origin (function, block and span) of every transition
+    pub node_map: bool,
+    /// fails on constructs that can put more than one token on a control flow place
+    /// (recursion and threads spawned in loops) and does not share subnets between calls
+    pub one_safe: bool,
+    /// readers that can hold a `RwLock` at the same time (`DEFAULT_READER_SLOTS` if not set)
+    pub rwlock_readers: Option<usize>,
+    /// calls below this depth of the call stack are modeled as opaque transitions
+    pub max_depth: Option<usize>,
+    /// prints the size of the net as JSON instead of plain text
+    pub stats_json: bool,
+    /// does not print the size of the net
+    pub quiet: bool,
+    /// prints the places of every translated function (see `Function::dump`)
+    pub debug_functions: bool,
 }
 
 pub struct Translator<'tcx> {
     tcx: TyCtxt<'tcx>,
+    // the name of the translated program (usually the crate name)
+    name: String,
     call_stack: CallStack<Function<'tcx>>,
-    visited: HashSet<DefId>,
-    net: PetriNet,
+    // function instances that were (or are being) translated
+    subnets: HashMap<(DefId, SubstsRef<'tcx>), Subnet>,
+    // function instances that are on the call stack
+    in_progress: HashSet<(DefId, SubstsRef<'tcx>)>,
+    // the generic substitutions of the functions on the call stack
+    frame_substs: Vec<SubstsRef<'tcx>>,
+    net: Net,
     mutex_list: MutexList,
+    channel_list: ChannelList,
+    arc_list: ArcList,
+    rwlock_list: RwLockList,
+    atomic_list: AtomicList,
+    condvar_list: CondvarList,
+    once_list: OnceList,
+    // join handles of spawned threads and the place that is marked when the thread finished
+    threads: HashMap<Local, NodeRef>,
+    // the captured locals of closure environments by environment and upvar index
+    upvars: HashMap<(Local, usize), Local>,
+    joined: HashSet<Local>,
+    // functions that were reified to function pointers
+    address_taken: Vec<DefId>,
     unwind_abort_place: NodeRef,
-    program_end_place: Option<NodeRef>,
-    mir_dump: Option<std::fs::File>,
+    // end of diverging functions, it has no outgoing arcs
+    diverged_place: NodeRef,
+    // the whole program stopped (std::process::exit or abort), shared by all functions
+    terminated_place: NodeRef,
+    // the Abort terminator (e.g. a panic while panicking), it has no outgoing arcs
+    aborted_place: NodeRef,
+    // reached Unreachable terminators if they are modeled (see `Options::unreachable_sink`)
+    unreachable_place: Option<NodeRef>,
+    // receives the MIR of every translated function in translation order
+    mir_dump: Option<Box<dyn std::io::Write>>,
+    watchdog: Option<Watchdog>,
+    options: Options,
+    // constructs that were left out of the net with their count and an example location
+    unsupported: BTreeMap<String, (usize, String)>,
+    // one place per `static` item that is shared by all functions
+    statics: HashMap<DefId, NodeRef>,
+    summaries: Summaries,
+    // the places of the resources that are used in the summaries
+    resources: HashMap<String, NodeRef>,
+    // origins of the transitions (only recorded if `Options::node_map` is set)
+    node_map: Vec<NodeSource>,
+    // the first error in a visitor method (they cannot return it)
+    error: Option<TranslationError>,
 }
 
 macro_rules! net {
@@ -66,83 +186,1798 @@ macro_rules! net {
     };
 }
 
+// The visitor only runs while a function is on the call stack, without one the
+// translation fails with `EmptyCallStack` (with a method instead of the macro the borrow
+// of the whole translator would conflict with the borrow of the net).
 macro_rules! function {
     ($translator:ident) => {
-        $translator.call_stack.peek_mut().expect("empty call stack")
+        $translator
+            .call_stack
+            .peek_mut()
+            .ok_or(TranslationError::EmptyCallStack)?
     };
 }
 
+// Collects the `static` items that are accessed in a body
+struct StaticCollector(Vec<DefId>);
+
+impl<'tcx> Visitor<'tcx> for StaticCollector {
+    fn visit_place_base(&mut self, base: &PlaceBase<'tcx>, _: PlaceContext, _: Location) {
+        if let PlaceBase::Static(statik) = base {
+            if let StaticKind::Static = statik.kind {
+                self.0.push(statik.def_id);
+            }
+        }
+    }
+}
+
+// a place with one token
+fn marked_place(net: &mut Net) -> petri_to_star::Result<NodeRef> {
+    let place = net.add_place();
+    net.mark(place, 1)?;
+    Ok(place)
+}
+
 impl<'tcx> Translator<'tcx> {
-    pub fn new(tcx: TyCtxt<'tcx>, mir_dump: Option<std::fs::File>) -> Result<Self> {
-        let mut net = PetriNet::new();
+    /// `name` is the name of the translated program, it prefixes the start and end place
+    /// and the paths of the local functions (usually `tcx.crate_name(LOCAL_CRATE)`).
+    pub fn new(
+        tcx: TyCtxt<'tcx>,
+        name: String,
+        mir_dump: Option<Box<dyn std::io::Write>>,
+        options: Options,
+    ) -> Result<Self> {
+        let mut net = Net::new();
         let unwind_abort_place = net.add_place();
-        unwind_abort_place.name(&mut net, "unwind_abort".into())?;
+        net.name(unwind_abort_place, "unwind_abort".into())?;
+        let diverged_place = net.add_place();
+        net.name(diverged_place, "diverged".into())?;
+        let terminated_place = net.add_place();
+        net.name(terminated_place, "program terminated".into())?;
+        let aborted_place = net.add_place();
+        net.name(aborted_place, "aborted".into())?;
+        let unreachable_place = if options.unreachable_sink {
+            let place = net.add_place();
+            net.name(place, "unreachable".into())?;
+            Some(place)
+        } else {
+            None
+        };
+        let summaries = match &options.summaries {
+            Some(path) => Summaries::load(path).map_err(TranslationError::Summary)?,
+            None => Summaries::default(),
+        };
+        let watchdog = options.watchdog.map(Watchdog::new);
+        if let Some(watchdog) = &watchdog {
+            net.report_progress(watchdog.progress());
+        }
+        let mut resources = HashMap::new();
+        for (name, tokens) in &summaries.resources {
+            let place = net.add_place();
+            net.name(place, name.clone())?;
+            net.mark(place, *tokens)?;
+            resources.insert(name.clone(), place);
+        }
         Ok(Translator {
             tcx,
+            name,
             call_stack: CallStack::new(),
-            visited: HashSet::new(),
+            subnets: HashMap::new(),
+            in_progress: HashSet::new(),
+            frame_substs: Vec::new(),
             net,
             mutex_list: MutexList::new(),
+            channel_list: ChannelList::new(),
+            arc_list: ArcList::new(),
+            rwlock_list: RwLockList::new(),
+            atomic_list: AtomicList::new(),
+            condvar_list: CondvarList::new(),
+            once_list: OnceList::new(),
+            threads: HashMap::new(),
+            upvars: HashMap::new(),
+            joined: HashSet::new(),
+            address_taken: Vec::new(),
             unwind_abort_place,
-            program_end_place: None,
+            diverged_place,
+            terminated_place,
+            aborted_place,
+            unreachable_place,
             mir_dump,
+            watchdog,
+            options,
+            unsupported: BTreeMap::new(),
+            statics: HashMap::new(),
+            summaries,
+            resources,
+            node_map: Vec::new(),
+            error: None,
         })
     }
 
     pub fn petrify(&mut self, main_fn: DefId) -> Result<&PetriNet> {
-        let start_place = {
+        self.petrify_entries(&[main_fn])
+    }
+
+    /// Translates every entry function into the same net, so they share statics and resources.
+    /// With more than one entry the (single marked) start place forks into a start place
+    /// per entry and the end place is marked when every entry returned.
+    pub fn petrify_entries(&mut self, entries: &[DefId]) -> Result<&PetriNet> {
+        if entries.is_empty() {
+            return Err(TranslationError::NoEntryFunction);
+        }
+        let start_place = marked_place(net!(self))?;
+        let program_end_place = {
+            let end_name = self.end_place_name();
             let net = net!(self);
             let place = net.add_place();
-            PlaceRef::try_from(place)?.marking(net, 1)?;
+            net.name(place, end_name)?;
             place
         };
-        self.program_end_place = {
+        net!(self).set_program_places(start_place, program_end_place);
+        if let [entry] = entries {
+            self.translate_entry(*entry, start_place, program_end_place)?;
+        } else {
             let net = net!(self);
-            let place = net.add_place();
-            place.name(net, "program end".into())?;
-            Some(place)
+            let fork = net.add_transition();
+            net.name(fork, "start entries".into())?;
+            net.add_arc(start_place, fork)?;
+            let join = net.add_transition();
+            net.name(join, "end entries".into())?;
+            net.add_arc(join, program_end_place)?;
+            for entry in entries {
+                let net = net!(self);
+                let entry_start = net.add_place();
+                let entry_end = net.add_place();
+                net.name(entry_end, format!("{} end", self.tcx.def_path_str(*entry)))?;
+                net.add_arc(fork, entry_start)?;
+                net.add_arc(entry_end, join)?;
+                self.translate_entry(*entry, entry_start, entry_end)?;
+            }
+        }
+        // the net has no name of its own, so its start and end are named after the crate
+        let start_name = self.start_place_name();
+        net!(self).name(start_place, start_name)?;
+        // translation is done, the watchdog is not needed anymore
+        self.watchdog = None;
+        self.report_unsupported();
+        let stats = self.stats();
+        // the JSON was asked for explicitly, so it is printed even in quiet mode
+        if self.options.stats_json {
+            let json = serde_json::to_string(&stats)
+                .map_err(|err| TranslationError::Stats(err.to_string()))?;
+            eprintln!("{}", json);
+        } else if self.options.quiet {
+            info!("net size: {}", stats);
+        } else {
+            eprintln!("net size: {}", stats);
+        }
+        Ok(self.net.petri_net())
+    }
+
+    fn translate_entry(&mut self, entry: DefId, start: NodeRef, end: NodeRef) -> Result<()> {
+        let data_return = Local::new(net!(self), "main_return")?;
+        self.translate(
+            entry,
+            InternalSubsts::identity_for_item(self.tcx, entry),
+            Vec::new(), //TODO: Arguments would be important for HiLvl Nets
+            data_return,
+            start,
+            end,
+            self.unwind_abort_place,
+        )
+    }
+
+    /// The net that was translated so far (with the record of its nodes)
+    pub fn net(&self) -> &Net {
+        &self.net
+    }
+
+    /// The translated net, e.g. to inspect it or to serialize it with the `petri_to_star` writers
+    pub fn into_net(self) -> PetriNet {
+        self.net.into_petri_net()
+    }
+
+    /// The name of the place that is marked in the initial marking
+    pub fn start_place_name(&self) -> String {
+        format!("{} start", self.name)
+    }
+
+    /// The name of the place that is marked when the entry function returns normally
+    pub fn end_place_name(&self) -> String {
+        format!("{} end", self.name)
+    }
+
+    /// The place that is marked in the initial marking (`None` before the translation)
+    pub fn program_start_place(&self) -> Option<NodeRef> {
+        self.net.start_place()
+    }
+
+    /// The place that is marked when the entry function (or every entry) returns normally
+    /// (`None` before the translation)
+    pub fn program_end_place(&self) -> Option<NodeRef> {
+        self.net.end_place()
+    }
+
+    /// The size of the net that was translated so far
+    pub fn stats(&self) -> NetStats {
+        NetStats::count(&self.net, self.subnets.len())
+    }
+
+    /// The origins of the transitions (empty if `Options::node_map` is not set)
+    pub fn node_map(&self) -> &[NodeSource] {
+        &self.node_map
+    }
+
+    pub fn mutex_count(&self) -> usize {
+        self.mutex_list.len()
+    }
+
+    /// The places that point to a concurrency issue if they can be marked, by kind:
+    /// a "deadlock" holds the locked place of a mutex forever and a "poison" marks
+    /// the poisoned place (a guard was dropped while unwinding).
+    /// Whether they are reachable is left to the model checker.
+    pub fn issue_places(&self) -> Vec<(&'static str, NodeRef)> {
+        let mut places = Vec::new();
+        for mutex in self.mutex_list.mutexes() {
+            places.push(("deadlock", mutex.locked(&self.mutex_list)));
+            places.push(("poison", mutex.poisoned(&self.mutex_list)));
+        }
+        places
+    }
+
+    // Remembers a construct that is not modeled in the net.
+    // In strict mode the translation is aborted instead.
+    fn unsupported(&mut self, kind: &str, source_info: SourceInfo) {
+        let location = self.tcx.sess.source_map().span_to_string(source_info.span);
+        if self.options.strict {
+            self.record::<(), _>(Err(TranslationError::UnsupportedTerminator(format!(
+                "{} at {}",
+                kind, location
+            ))));
+            return;
+        }
+        warn!("unsupported {} at {}", kind, location);
+        self.unsupported
+            .entry(kind.into())
+            .or_insert((0, location))
+            .0 += 1;
+    }
+
+    fn report_unsupported(&self) {
+        if self.unsupported.is_empty() {
+            info!("all constructs were translated");
+            return;
+        }
+        warn!("the following constructs are not modeled in the net:");
+        for (kind, (count, example)) in &self.unsupported {
+            warn!("    {} x{} (e.g. at {})", kind, count, example);
+        }
+    }
+
+    // Keeps the first error of a visitor method, it is returned after the body was visited
+    fn record<T, E: Into<TranslationError>>(
+        &mut self,
+        result: std::result::Result<T, E>,
+    ) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                let err = err.into();
+                error!("{}", err);
+                if self.error.is_none() {
+                    self.error = Some(err);
+                }
+                None
+            }
+        }
+    }
+
+    fn translate<'a>(
+        &mut self,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+        args: Vec<Local>,
+        data_return: Local,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        unwind_flow: NodeRef,
+    ) -> Result<()> {
+        let fn_name = self.tcx.def_path_str(function);
+        self.net.name(start_place, fn_name.clone())?;
+        if Self::is_unique(&fn_name) {
+            self.translate_unique(
+                function,
+                args,
+                data_return,
+                start_place,
+                return_flow,
+                fn_name,
+            )?
+        } else {
+            self.translate_default(
+                function,
+                substs,
+                args,
+                data_return,
+                start_place,
+                return_flow,
+                unwind_flow,
+                fn_name,
+            )?
+        }
+        Ok(())
+    }
+
+    /// The `Drop::drop` implementation of the given type if it is defined in the local crate.
+    /// Destructors of foreign types (e.g. all of std) are not translated.
+    fn user_destructor(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Option<DefId> {
+        match ty.ty_adt_def().and_then(|adt| adt.destructor(tcx)) {
+            Some(destructor) if destructor.did.is_local() => Some(destructor.did),
+            _ => None,
+        }
+    }
+
+    fn translate_drop_glue(
+        &mut self,
+        destructor: DefId,
+        location: &Place<'tcx>,
+        target: BasicBlock,
+        unwind: Option<BasicBlock>,
+    ) -> Result<()> {
+        let net = net!(self);
+        let start_place = function!(self).function_call_start_place()?;
+        let return_place = function!(self).get_basic_block_start(net, target)?;
+        let unwind_place = function!(self).unwind_target(net, unwind)?;
+        // `Drop::drop` only takes `&mut self`
+        let args = vec![function!(self).place_to_local(location)?];
+        let data_return = Local::new(net, "drop_return")?;
+        self.translate(
+            destructor,
+            InternalSubsts::identity_for_item(self.tcx, destructor),
+            args,
+            data_return,
+            start_place,
+            return_place,
+            unwind_place,
+        )
+    }
+
+    fn translate_drop(
+        &mut self,
+        location: &Place<'tcx>,
+        target: BasicBlock,
+        unwind: Option<BasicBlock>,
+    ) -> Result<()> {
+        let ty = {
+            let function = self
+                .call_stack
+                .peek()
+                .ok_or(TranslationError::EmptyCallStack)?;
+            location.ty(function.mir_body.local_decls(), self.tcx).ty
+        };
+        let ty = Self::monomorphize(self.tcx, &self.frame_substs, ty);
+        if let Some(destructor) = Self::user_destructor(self.tcx, ty) {
+            return self.translate_drop_glue(destructor, location, target, unwind);
+        }
+        let net = net!(self);
+        let t = function!(self).drop(net, target, unwind)?;
+        // dropping a guard unlocks its mutex (and poisons it during a panic)
+        let local = function!(self).place_to_local(location)?;
+        if let Some(mutex) = self.mutex_list.get_guard(local).copied() {
+            debug!("unlock mutex '{:?}'", mutex);
+            net.add_arc(mutex.locked(&self.mutex_list), t)?;
+            net.add_arc(t, mutex.unlocked(&self.mutex_list))?;
+            if function!(self).in_cleanup_block() {
+                debug!("poison mutex '{:?}'", mutex);
+                net.add_arc(t, mutex.poisoned(&self.mutex_list))?;
+            }
+        }
+        // dropping a rwlock guard gives its slot (or the writer token) back
+        if let Some((lock, write)) = self.rwlock_list.get_guard(local).copied() {
+            debug!("unlock rwlock '{:?}'", lock);
+            if write {
+                net.add_arc(t, lock.releasing(&self.rwlock_list))?;
+            } else {
+                net.add_arc(t, lock.slots(&self.rwlock_list))?;
+            }
+        }
+        if let Some(arc) = self.arc_list.get_linked(local).copied() {
+            self.translate_arc_drop(arc, local, t, target)?;
+        }
+        let senders = self.dropped_senders(ty, local);
+        if !senders.is_empty() {
+            self.translate_sender_drop(&senders, t, target)?;
+        }
+        // a boxed mutex is destroyed with its box (unlike an `Arc` a box has one owner)
+        if ty.is_box() && self.arc_list.get_linked(local).is_none() {
+            let boxes_mutex = match ty.boxed_ty().ty_adt_def() {
+                Some(adt) => self.tcx.def_path_str(adt.did) == "std::sync::Mutex",
+                None => false,
+            };
+            if let (true, Some(mutex)) = (boxes_mutex, self.mutex_list.is_linked(local).copied()) {
+                debug!("destroy mutex '{:?}'", mutex);
+                net.add_arc(mutex.unlocked(&self.mutex_list), t)?;
+                net.add_arc(t, mutex.dead(&self.mutex_list))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn translate_call(
+        &mut self,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+        cleanup: Option<BasicBlock>,
+    ) -> Result<()> {
+        let (function, substs) = Self::resolve(self.tcx, function, substs);
+        let net = net!(self);
+        let fn_name = self.tcx.def_path_str(function);
+        // calls that are modeled without a body cannot panic on their own,
+        // so their unwinding path has to be added here
+        if Self::is_thread_spawn(self.tcx, function) {
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            self.translate_spawn(substs, args, destination)?;
+        } else if Self::is_thread_join(self.tcx, function) {
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            self.translate_join(args, destination)?;
+        } else if let Some(channel_function) = Self::channel_function(self.tcx, function) {
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            self.translate_channel(channel_function, args, destination)?;
+        } else if let Some(arc_function) = Self::arc_function(self.tcx, function) {
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            self.translate_arc(arc_function, args, destination)?;
+        } else if let Some(rwlock_function) = Self::rwlock_function(self.tcx, function) {
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            self.translate_rwlock(rwlock_function, args, destination)?;
+        } else if let Some(atomic_function) = Self::atomic_function(self.tcx, function) {
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            self.translate_atomic(atomic_function, &fn_name, args, destination)?;
+        } else if let Some(condvar_function) = Self::condvar_function(self.tcx, function) {
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            self.translate_condvar(condvar_function, args, destination)?;
+        } else if let Some(once_function) = Self::once_function(self.tcx, function) {
+            // the initialization unwinds on its own
+            self.translate_once(once_function, substs, args, destination, cleanup)?;
+        } else if Self::is_box_allocation(self.tcx, function) {
+            // the allocator internals are not translated, the boxed value keeps its
+            // links through the argument of `Box::new` (see `visit_terminator_kind`)
+            function!(self).opaque_call(net, &fn_name, args, destination, cleanup)?;
+        } else if Self::is_scheduling_point(self.tcx, function) {
+            // only a point where other threads can run, the std internals are not translated.
+            // The net library has no timed transitions, so the duration of a sleep is lost.
+            let (_, continuation) = destination.as_ref().ok_or_else(|| {
+                TranslationError::UnexpectedMir(format!("{} without destination", fn_name))
+            })?;
+            function!(self).call_transition(net, fn_name, *continuation)?;
+        } else if Self::is_process_exit(self.tcx, function) {
+            info!("{} terminates the program", fn_name);
+            function!(self).terminate(net, &fn_name, self.terminated_place)?;
+        } else if let Some((closure, closure_substs)) =
+            Self::closure_body(self.tcx, function, substs)
+        {
+            // the environment with the captured upvars is the first argument of the body
+            debug!("resolved closure call to {:?}", closure);
+            let start_place = function!(self).function_call_start_place()?;
+            self.translate_callee(
+                closure,
+                closure_substs,
+                args,
+                destination,
+                cleanup,
+                start_place,
+            )?;
+        } else if let Some(candidates) =
+            Self::dyn_candidates(self.tcx, &self.options, function, substs)
+        {
+            info!(
+                "dynamic call of {} with {} candidates",
+                fn_name,
+                candidates.len()
+            );
+            for candidate in candidates {
+                let start_place = net!(self).add_place();
+                function!(self).branch(net!(self), start_place)?;
+                let candidate_substs = InternalSubsts::identity_for_item(self.tcx, candidate);
+                self.translate_callee(
+                    candidate,
+                    candidate_substs,
+                    args,
+                    destination,
+                    cleanup,
+                    start_place,
+                )?;
+            }
+        } else if let (Some(summary), Some((_, continuation))) =
+            (self.summaries.get(&fn_name).cloned(), destination)
+        {
+            info!("applying the summary of {}", fn_name);
+            function!(self).call_unwind(net, &fn_name, cleanup)?;
+            if summary.spawn {
+                return self.translate_spawn(substs, args, destination);
+            }
+            let t = function!(self).call_transition(net, summary.path.clone(), *continuation)?;
+            for resource in &summary.consume {
+                net.add_arc(self.resources[resource], t)?;
+            }
+            for resource in &summary.produce {
+                net.add_arc(t, self.resources[resource])?;
+            }
+        } else if Self::skip_function(&self.options, &fn_name) {
+            info!("treating {} as atomic", fn_name);
+            function!(self).opaque_call(net, &fn_name, args, destination, cleanup)?;
+        } else if Self::exceeds_depth(&self.options, self.call_stack.len())
+            && !Self::matches_path(&self.options.force_inline, &fn_name)
+            && !Self::is_unique(&fn_name)
+            && !Self::is_panic(self.tcx, function)
+        {
+            // mutex functions and panics keep their own model, they add no subnet
+            info!("maximum depth reached, treating {} as atomic", fn_name);
+            function!(self).opaque_call(net, &fn_name, args, destination, cleanup)?;
+        } else if !Self::is_panic(self.tcx, function) {
+            if self.tcx.is_foreign_item(function) || !self.tcx.is_mir_available(function) {
+                info!("emulating mir-less item {:?}", function);
+                function!(self).emulate_foreign(
+                    net,
+                    &fn_name,
+                    args,
+                    destination,
+                    cleanup,
+                    self.unwind_abort_place,
+                )?;
+            } else {
+                if Self::is_unique(&fn_name) {
+                    function!(self).call_unwind(net, &fn_name, cleanup)?;
+                }
+                let start_place = function!(self).function_call_start_place()?;
+                self.translate_callee(function, substs, args, destination, cleanup, start_place)?;
+            }
+        } else {
+            function!(self).handle_panic(net, self.unwind_abort_place)?;
+        }
+        Ok(())
+    }
+
+    /// The function that is called for the (monomorphic) substitutions of the call site.
+    /// Calls that cannot be resolved to an item with a body (e.g. virtual calls, shims or
+    /// calls in generic functions) stay as they are.
+    fn resolve(
+        tcx: TyCtxt<'tcx>,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+    ) -> (DefId, SubstsRef<'tcx>) {
+        let resolved = if substs.needs_subst() {
+            None
+        } else {
+            match ty::Instance::resolve(tcx, ty::ParamEnv::reveal_all(), function, substs) {
+                Some(ty::Instance {
+                    def: ty::InstanceDef::Item(def_id),
+                    substs,
+                }) => Some((def_id, substs)),
+                _ => None,
+            }
+        };
+        match resolved {
+            Some((def_id, substs)) if def_id != function => {
+                debug!("resolved {:?} to {:?}", function, def_id);
+                (def_id, substs)
+            }
+            // the trait default (or nothing if the trait item has no body) is translated
+            _ if tcx.trait_of_item(function).is_some() => {
+                debug!(
+                    "{} is not resolved to an impl, falling back to the trait item",
+                    tcx.def_path_str(function)
+                );
+                resolved.unwrap_or((function, substs))
+            }
+            _ => resolved.unwrap_or((function, substs)),
+        }
+    }
+
+    // The type in the context of the function on top of the call stack
+    // (with all known generic parameters substituted).
+    fn monomorphize(tcx: TyCtxt<'tcx>, frame_substs: &[SubstsRef<'tcx>], ty: Ty<'tcx>) -> Ty<'tcx> {
+        let ty = match frame_substs.last() {
+            Some(substs) => ty.subst(tcx, substs),
+            None => ty,
+        };
+        if ty.needs_subst() {
+            ty
+        } else {
+            tcx.normalize_erasing_regions(ty::ParamEnv::reveal_all(), ty)
+        }
+    }
+
+    /// The body of the closure that is called through `Fn::call`, `FnMut::call_mut`
+    /// or `FnOnce::call_once`
+    fn closure_body(
+        tcx: TyCtxt<'tcx>,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+    ) -> Option<(DefId, SubstsRef<'tcx>)> {
+        let lang_items = tcx.lang_items();
+        let fn_traits = [
+            lang_items.fn_trait(),
+            lang_items.fn_mut_trait(),
+            lang_items.fn_once_trait(),
+        ];
+        match tcx.trait_of_item(function) {
+            Some(fn_trait) if fn_traits.contains(&Some(fn_trait)) => match substs.type_at(0).kind {
+                ty::Closure(def_id, closure_substs) => Some((def_id, closure_substs)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The implementations of the local crate that can be the target of a call of a trait
+    /// method on a trait object (only with `resolve_dyn`)
+    fn dyn_candidates(
+        tcx: TyCtxt<'tcx>,
+        options: &Options,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+    ) -> Option<Vec<DefId>> {
+        if !options.resolve_dyn {
+            return None;
+        }
+        let trait_id = tcx.trait_of_item(function)?;
+        match substs.type_at(0).kind {
+            ty::Dynamic(..) => {}
+            _ => return None,
+        }
+        let method = tcx.associated_item(function).ident.name;
+        let candidates: Vec<DefId> = tcx
+            .all_impls(trait_id)
+            .into_iter()
+            .filter(|def_id| def_id.is_local())
+            .flat_map(|impl_id| tcx.associated_item_def_ids(impl_id).iter().copied())
+            .filter(|item| tcx.associated_item(*item).ident.name == method)
+            .filter(|item| tcx.is_mir_available(*item))
+            .collect();
+        if candidates.is_empty() {
+            warn!(
+                "no implementation found for the dynamic call of {}",
+                tcx.def_path_str(function)
+            );
+            return None;
+        }
+        Some(candidates)
+    }
+
+    // translates the body of the called function starting from the given place
+    fn translate_callee(
+        &mut self,
+        function: DefId,
+        substs: SubstsRef<'tcx>,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+        cleanup: Option<BasicBlock>,
+        start_place: NodeRef,
+    ) -> Result<()> {
+        let net = net!(self);
+        let (data_return, return_place) = match destination {
+            Some((return_place, return_block)) => {
+                let return_local = return_place.local_or_deref_local().ok_or_else(|| {
+                    TranslationError::UnexpectedMir(format!("return place {:?}", return_place))
+                })?;
+                let data_return = *function!(self).get_local(&return_local).ok_or_else(|| {
+                    TranslationError::UnexpectedMir("return local not found".into())
+                })?;
+                let return_place = function!(self).get_basic_block_start(net, *return_block)?;
+                (data_return, return_place)
+            }
+            // a diverging function never returns (e.g. `std::process::exit`),
+            // if its body ends anyway the execution stops in the sink place
+            None => {
+                info!("{} diverges", self.tcx.def_path_str(function));
+                let data_return = Local::new(net, "diverged_return")?;
+                (data_return, self.diverged_place)
+            }
+        };
+        let stack_top = function!(self); // needed in the closure
+        let args = args
+            .iter()
+            .map(|operand| stack_top.op_to_local(operand))
+            .collect::<Result<_>>()?;
+        // a panic in the callee continues in our cleanup block
+        // or, without cleanup, unwinds our function as well
+        let unwind_place = function!(self).unwind_target(net, cleanup)?;
+        self.translate(
+            function,
+            substs,
+            args,
+            data_return,
+            start_place,
+            return_place,
+            unwind_place,
+        )
+    }
+
+    /// Calls through a function pointer are resolved with the functions that were
+    /// reified into the pointer local. Otherwise (if enabled) every function whose address
+    /// was taken is a candidate that is called nondeterministically.
+    /// Without candidates the call is an opaque transition.
+    fn translate_fn_pointer_call(
+        &mut self,
+        func: &Operand<'tcx>,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+        cleanup: Option<BasicBlock>,
+    ) -> Result<()> {
+        let resolved = match func {
+            Operand::Copy(place) | Operand::Move(place) => match &place.base {
+                PlaceBase::Local(local) if place.projection.is_empty() => {
+                    function!(self).fn_pointer(local)
+                }
+                _ => None,
+            },
+            Operand::Constant(_) => None,
+        };
+        if let Some(function) = resolved {
+            debug!("resolved function pointer to {:?}", function);
+            let substs = InternalSubsts::identity_for_item(self.tcx, function);
+            return self.translate_call(function, substs, args, destination, cleanup);
+        }
+        let candidates = if self.options.fn_pointer_candidates {
+            self.address_taken.clone()
+        } else {
+            Vec::new()
+        };
+        if destination.is_some() && !candidates.is_empty() {
+            info!("function pointer call with {} candidates", candidates.len());
+            for candidate in candidates {
+                let start_place = net!(self).add_place();
+                function!(self).branch(net!(self), start_place)?;
+                let candidate_substs = InternalSubsts::identity_for_item(self.tcx, candidate);
+                self.translate_callee(
+                    candidate,
+                    candidate_substs,
+                    args,
+                    destination,
+                    cleanup,
+                    start_place,
+                )?;
+            }
+        } else {
+            warn!("unresolved function pointer call is modeled as opaque transition");
+            function!(self).opaque_call(
+                net!(self),
+                "function pointer call",
+                args,
+                destination,
+                cleanup,
+            )?;
+        }
+        Ok(())
+    }
+
+    // The bits of a boolean, integer or char constant.
+    // A const generic parameter is known in the context of the function on top of the
+    // call stack (each instance has its own subnet).
+    fn constant_bits(&self, constant: &Constant<'tcx>) -> Option<u128> {
+        let literal = match self.frame_substs.last() {
+            Some(substs) => constant.literal.subst(self.tcx, substs),
+            None => constant.literal,
+        };
+        let ty = literal.ty;
+        if ty.is_bool() || ty.is_integral() || ty.is_char() {
+            literal.try_eval_bits(self.tcx, ty::ParamEnv::reveal_all(), ty)
+        } else {
+            None
+        }
+    }
+
+    // The discriminant value (not the index) of the variant of the enum place
+    // if the variant was set before in the active block
+    fn known_discriminant(&self, source: &Place<'tcx>) -> Option<u128> {
+        let function = self.call_stack.peek()?;
+        let variant = function.variant(source)?;
+        let ty = source.ty(&function.mir_body.local_decls, self.tcx).ty;
+        ty.discriminant_for_variant(self.tcx, VariantIdx::from_usize(variant))
+            .map(|discriminant| discriminant.val)
+    }
+
+    // Remembers constants that are assigned to locals for the pruning of SwitchInt branches.
+    // The discriminant of an enum with a known variant is known as well.
+    // Every other assignment makes the value of the local unknown.
+    fn track_known_value(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>) -> Result<()> {
+        let value = match rvalue {
+            Rvalue::Use(Operand::Constant(constant)) => self.constant_bits(constant),
+            Rvalue::Use(operand) => function!(self).known_value(operand),
+            Rvalue::Discriminant(source) => self.known_discriminant(source),
+            _ => None,
+        };
+        let function = function!(self);
+        // the enum is overwritten (the variant of a field write stays the same)
+        let variant = match rvalue {
+            Rvalue::Aggregate(box AggregateKind::Adt(adt, variant, ..), _) if adt.is_enum() => {
+                Some(variant.as_usize())
+            }
+            _ => None,
+        };
+        function.set_variant(place, variant);
+        if let Rvalue::Ref(_, _, source) | Rvalue::AddressOf(_, source) = rvalue {
+            // the local can be changed through the reference
+            if let PlaceBase::Local(local) = source.base {
+                function.set_known_value(local, None);
+            }
+        }
+        match (&place.base, place.projection.is_empty()) {
+            (PlaceBase::Local(local), true) => function.set_known_value(*local, value),
+            // a write through a pointer can change any local
+            _ if place.is_indirect() => function.forget_known_values(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // The local whose links a read of the place passes on.
+    // A field of a closure environment (`_1.0` or `(*_1).0` in the closure body) is the
+    // upvar that was captured by the closure, so the body of a spawned thread uses the same
+    // resources as its parent even if it captures several of them. Otherwise it is the
+    // local of the place.
+    fn link_source(
+        function: &Function<'tcx>,
+        upvars: &HashMap<(Local, usize), Local>,
+        place: &Place<'tcx>,
+    ) -> Result<Local> {
+        let local = function.place_to_local(place)?;
+        let field = place
+            .projection
+            .iter()
+            .skip_while(|elem| match elem {
+                ProjectionElem::Deref => true,
+                _ => false,
+            })
+            .next();
+        Ok(match field {
+            Some(ProjectionElem::Field(field, _)) => upvars
+                .get(&(local, field.index()))
+                .copied()
+                .unwrap_or(local),
+            _ => local,
+        })
+    }
+
+    fn operand_link_source(
+        function: &Function<'tcx>,
+        upvars: &HashMap<(Local, usize), Local>,
+        operand: &Operand<'tcx>,
+    ) -> Result<Local> {
+        match operand {
+            Operand::Copy(place) | Operand::Move(place) => {
+                Self::link_source(function, upvars, place)
+            }
+            Operand::Constant(_) => function.op_to_local(operand),
+        }
+    }
+
+    /// Passes the resources (mutexes, guards, join handles, channels) that are linked to
+    /// `from` on to `to`.
+    // With the links an alias (`let m2 = m1;`, `let r = &m;`, a deref `*r`) locks the
+    // same resource place. The aliasing is approximated on whole locals:
+    // - a place is reduced to its base local, so every field and deref of `r` is `r`
+    //   (two resources in one struct cannot be told apart, only the fields of a closure
+    //   environment are resolved to the captured upvars, see `link_source`)
+    // - links are only added, a local that is overwritten keeps its old resource
+    // - aliases created through raw pointer arithmetic or unsafe casts are not seen
+    fn propagate_links(&mut self, from: Local, to: Local) {
+        if let Some(mutex) = self.mutex_list.is_linked(from).copied() {
+            debug!("link '{:?}' to mutex '{:?}'", to, mutex);
+            self.mutex_list.link(to, mutex)
+        }
+        // e.g. the guard that is unwrapped from the LockResult
+        if let Some(mutex) = self.mutex_list.get_guard(from).copied() {
+            debug!("'{:?}' guards mutex '{:?}'", to, mutex);
+            self.mutex_list.add_guard(to, mutex)
+        }
+        // join handles can be moved before they are joined
+        if let Some(thread_end) = self.threads.get(&from).copied() {
+            self.threads.insert(to, thread_end);
+        }
+        // sender and receiver are moved out of the tuple that is returned by `channel()`
+        if let Some(channel) = self.channel_list.get_linked(from).copied() {
+            debug!("link '{:?}' to channel '{:?}'", to, channel);
+            self.channel_list.link(to, channel)
+        }
+        if let Some(arc) = self.arc_list.get_linked(from).copied() {
+            debug!("link '{:?}' to arc '{:?}'", to, arc);
+            self.arc_list.link(to, arc)
+        }
+        if let Some(lock) = self.rwlock_list.get_linked(from).copied() {
+            debug!("link '{:?}' to rwlock '{:?}'", to, lock);
+            self.rwlock_list.link(to, lock)
+        }
+        if let Some((lock, write)) = self.rwlock_list.get_guard(from).copied() {
+            debug!("'{:?}' guards rwlock '{:?}'", to, lock);
+            self.rwlock_list.add_guard(to, lock, write)
+        }
+        if let Some(atomic) = self.atomic_list.get_linked(from).copied() {
+            debug!("link '{:?}' to atomic '{:?}'", to, atomic);
+            self.atomic_list.link(to, atomic)
+        }
+        if let Some(condvar) = self.condvar_list.get_linked(from).copied() {
+            debug!("link '{:?}' to condvar '{:?}'", to, condvar);
+            self.condvar_list.link(to, condvar)
+        }
+        if let Some(once) = self.once_list.get_linked(from).copied() {
+            debug!("link '{:?}' to once '{:?}'", to, once);
+            self.once_list.link(to, once)
+        }
+    }
+
+    fn arc_function(tcx: TyCtxt<'_>, function: DefId) -> Option<ArcFunction> {
+        match tcx.def_path_str(function) {
+            name if name.contains("std::sync::Arc::<T>::new") => Some(ArcFunction::New),
+            name if name.contains("<std::sync::Arc<T> as std::clone::Clone>::clone") => {
+                Some(ArcFunction::Clone)
+            }
+            _ => None,
+        }
+    }
+
+    /// An `Arc` is a place with one token per reference.
+    /// `new` and `clone` add a reference, dropping an `Arc` removes one (see `translate_arc_drop`).
+    /// The destination shares the resources of the argument, e.g. the mutex inside the `Arc`.
+    fn translate_arc(
+        &mut self,
+        arc_function: ArcFunction,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) -> Result<()> {
+        let (place, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("arc function without destination".into())
+        })?;
+        let name = format!("Arc::{:?}", arc_function);
+        let t = function!(self).call_transition(net!(self), name, *continuation)?;
+        let arg = args
+            .get(0)
+            .ok_or_else(|| TranslationError::UnexpectedMir("no arc argument".into()))?;
+        let arg = function!(self).op_to_local(arg)?;
+        let local = function!(self).place_to_local(place)?;
+        let arc = match arc_function {
+            ArcFunction::New => {
+                let arc = self.arc_list.add(net!(self))?;
+                net!(self).add_arc(t, arc.alive(&self.arc_list))?;
+                arc
+            }
+            ArcFunction::Clone => self.arc_for(arg)?,
+        };
+        net!(self).add_arc(t, arc.count(&self.arc_list))?;
+        self.propagate_links(arg, local);
+        debug!("link '{:?}' to arc '{:?}'", local, arc);
+        self.arc_list.link(local, arc);
+        Ok(())
+    }
+
+    // Dropping an `Arc` removes a reference. The net cannot test the count for zero,
+    // so the drop of the last reference (that drops the inner value and kills its mutex)
+    // is a nondeterministic alternative to the plain drop.
+    fn translate_arc_drop(
+        &mut self,
+        arc: ArcRef,
+        local: Local,
+        drop: NodeRef,
+        target: BasicBlock,
+    ) -> Result<()> {
+        let last = function!(self).drop(net!(self), target, None)?;
+        let net = net!(self);
+        function!(self).rename_transition(net, last, "drop last Arc".into())?;
+        let count = arc.count(&self.arc_list);
+        let alive = arc.alive(&self.arc_list);
+        net.add_arc(count, drop)?;
+        net.add_arc(count, last)?;
+        net.add_arc(alive, last)?;
+        if let Some(mutex) = self.mutex_list.is_linked(local).copied() {
+            debug!("destroy mutex '{:?}'", mutex);
+            net.add_arc(mutex.unlocked(&self.mutex_list), last)?;
+            net.add_arc(last, mutex.dead(&self.mutex_list))?;
+        }
+        Ok(())
+    }
+
+    // The arc that is linked to the local.
+    // If the identity cannot be resolved a new arc with one reference is assumed for the local.
+    fn arc_for(&mut self, local: Local) -> Result<ArcRef> {
+        match self.arc_list.get_linked(local) {
+            Some(arc) => Ok(*arc),
+            None => {
+                warn!(
+                    "cannot resolve the arc of '{:?}', using a new arc instead",
+                    local
+                );
+                let arc = self.arc_list.add_referenced(&mut self.net)?;
+                self.arc_list.link(local, arc);
+                Ok(arc)
+            }
+        }
+    }
+
+    fn rwlock_function(tcx: TyCtxt<'_>, function: DefId) -> Option<RwLockFunction> {
+        match tcx.def_path_str(function) {
+            name if name.contains("std::sync::RwLock::<T>::new") => Some(RwLockFunction::New),
+            name if name.contains("std::sync::RwLock::<T>::read") => Some(RwLockFunction::Read),
+            name if name.contains("std::sync::RwLock::<T>::write") => Some(RwLockFunction::Write),
+            name if name.contains("std::sync::RwLock::<T>::try_read") => {
+                Some(RwLockFunction::TryRead)
+            }
+            name if name.contains("std::sync::RwLock::<T>::try_write") => {
+                Some(RwLockFunction::TryWrite)
+            }
+            _ => None,
+        }
+    }
+
+    /// A `RwLock` has a writer token and one slot per reader.
+    /// A reader takes a slot if no writer holds the lock,
+    /// a writer takes the writer token and then waits for every slot.
+    /// `try_read` and `try_write` can succeed like `read` and `write` or fail
+    /// without touching the lock. Without inhibitor arcs the failure cannot check
+    /// that the lock is held, so it can happen spuriously. A `try_write` that got the
+    /// writer token still waits for the readers to leave.
+    fn translate_rwlock(
+        &mut self,
+        rwlock_function: RwLockFunction,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) -> Result<()> {
+        let (place, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("rwlock function without destination".into())
+        })?;
+        let local = function!(self).place_to_local(place)?;
+        if let RwLockFunction::New = rwlock_function {
+            function!(self).call_transition(net!(self), "RwLock::new".into(), *continuation)?;
+            let readers = self.options.rwlock_readers.unwrap_or(DEFAULT_READER_SLOTS);
+            let lock = self.rwlock_list.add(net!(self), readers)?;
+            debug!("link '{:?}' to rwlock '{:?}'", local, lock);
+            self.rwlock_list.link(local, lock);
+            return Ok(());
+        }
+        let lock = args
+            .get(0)
+            .ok_or_else(|| TranslationError::UnexpectedMir("no rwlock argument".into()))?;
+        let lock = function!(self).op_to_local(lock)?;
+        let lock = self.rwlock_for(lock)?;
+        let writer = lock.writer(&self.rwlock_list);
+        let slots = lock.slots(&self.rwlock_list);
+        let name = match rwlock_function {
+            RwLockFunction::Read => "RwLock::read",
+            RwLockFunction::Write => "RwLock::write",
+            RwLockFunction::TryRead => "RwLock::try_read",
+            RwLockFunction::TryWrite => "RwLock::try_write",
+            // handled above
+            RwLockFunction::New => {
+                return Err(TranslationError::UnexpectedMir(
+                    "RwLock::new is not a lock operation".into(),
+                ))
+            }
+        };
+        match rwlock_function {
+            RwLockFunction::Read | RwLockFunction::TryRead => {
+                let t = function!(self).call_transition(net!(self), name.into(), *continuation)?;
+                let net = net!(self);
+                net.add_arc(writer, t)?;
+                net.add_arc(t, writer)?;
+                net.add_arc(slots, t)?;
+                self.rwlock_list.add_guard(local, lock, false);
+            }
+            RwLockFunction::Write | RwLockFunction::TryWrite => {
+                let readers = lock.readers(&self.rwlock_list);
+                let source = function!(self).function_call_start_place()?;
+                let target = function!(self).get_basic_block_start(net!(self), *continuation)?;
+                let net = net!(self);
+                let t = net.add_transition();
+                function!(self).name_transition(net, t, name.into())?;
+                net.add_arc(source, t)?;
+                net.add_arc(writer, t)?;
+                let mut waiting = t;
+                for slot in 0..readers {
+                    let place = net.add_place();
+                    net.add_arc(waiting, place)?;
+                    waiting = net.add_transition();
+                    function!(self).name_transition(
+                        net,
+                        waiting,
+                        format!("{} slot {}", name, slot),
+                    )?;
+                    net.add_arc(place, waiting)?;
+                    net.add_arc(slots, waiting)?;
+                }
+                net.add_arc(waiting, target)?;
+                self.rwlock_list.add_guard(local, lock, true);
+            }
+            RwLockFunction::New => {
+                return Err(TranslationError::UnexpectedMir(
+                    "RwLock::new is not a lock operation".into(),
+                ))
+            }
+        }
+        if let RwLockFunction::TryRead | RwLockFunction::TryWrite = rwlock_function {
+            function!(self).call_transition(
+                net!(self),
+                format!("{} failed", name),
+                *continuation,
+            )?;
+        }
+        Ok(())
+    }
+
+    // The rwlock that is linked to the local.
+    // If the identity cannot be resolved a new (unlocked) rwlock is assumed for the local.
+    fn rwlock_for(&mut self, local: Local) -> Result<RwLockRef> {
+        match self.rwlock_list.get_linked(local) {
+            Some(lock) => Ok(*lock),
+            None => {
+                warn!(
+                    "cannot resolve the rwlock of '{:?}', using a new rwlock instead",
+                    local
+                );
+                let readers = self.options.rwlock_readers.unwrap_or(DEFAULT_READER_SLOTS);
+                let lock = self.rwlock_list.add(&mut self.net, readers)?;
+                self.rwlock_list.link(local, lock);
+                Ok(lock)
+            }
+        }
+    }
+
+    fn atomic_function(tcx: TyCtxt<'_>, function: DefId) -> Option<AtomicFunction> {
+        let name = tcx.def_path_str(function);
+        if !name.starts_with("std::sync::atomic::Atomic") {
+            return None;
+        }
+        match name.rsplit("::").next() {
+            Some("new") => Some(AtomicFunction::New),
+            Some("load") => Some(AtomicFunction::Load),
+            Some("store") => Some(AtomicFunction::Store),
+            Some("compare_exchange") | Some("compare_exchange_weak") | Some("compare_and_swap") => {
+                Some(AtomicFunction::CompareExchange)
+            }
+            Some(method) if method == "swap" || method.starts_with("fetch_") => {
+                Some(AtomicFunction::ReadModifyWrite)
+            }
+            _ => None,
+        }
+    }
+
+    /// Every operation on an atomic accesses its place exclusively.
+    /// A compare exchange branches into success and failure, the net does not know the value.
+    /// Both branches only need the token of the atomic, so the success of a retry loop
+    /// (`while compare_exchange(..).is_err() {}`) is enabled whenever no other thread
+    /// accesses the atomic and the loop cannot look like a livelock of the net.
+    fn translate_atomic(
+        &mut self,
+        atomic_function: AtomicFunction,
+        fn_name: &str,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) -> Result<()> {
+        let (place, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("atomic function without destination".into())
+        })?;
+        let atomic = match atomic_function {
+            AtomicFunction::New => {
+                let atomic = self.atomic_list.add(net!(self))?;
+                let local = function!(self).place_to_local(place)?;
+                debug!("link '{:?}' to atomic '{:?}'", local, atomic);
+                self.atomic_list.link(local, atomic);
+                atomic
+            }
+            _ => {
+                let arg = args
+                    .get(0)
+                    .ok_or_else(|| TranslationError::UnexpectedMir("no atomic argument".into()))?;
+                let arg = function!(self).op_to_local(arg)?;
+                self.atomic_for(arg)?
+            }
+        };
+        let value = atomic.value(&self.atomic_list);
+        let names = match atomic_function {
+            AtomicFunction::CompareExchange => vec![
+                format!("{} success", fn_name),
+                format!("{} failure", fn_name),
+            ],
+            _ => vec![fn_name.to_string()],
+        };
+        for name in names {
+            let t = function!(self).call_transition(net!(self), name, *continuation)?;
+            let net = net!(self);
+            net.add_arc(value, t)?;
+            net.add_arc(t, value)?;
+        }
+        Ok(())
+    }
+
+    // The atomic that is linked to the local.
+    // If the identity cannot be resolved a new atomic is assumed for the local.
+    fn atomic_for(&mut self, local: Local) -> Result<AtomicRef> {
+        match self.atomic_list.get_linked(local) {
+            Some(atomic) => Ok(*atomic),
+            None => {
+                warn!(
+                    "cannot resolve the atomic of '{:?}', using a new atomic instead",
+                    local
+                );
+                let atomic = self.atomic_list.add(&mut self.net)?;
+                self.atomic_list.link(local, atomic);
+                Ok(atomic)
+            }
+        }
+    }
+
+    fn condvar_function(tcx: TyCtxt<'_>, function: DefId) -> Option<CondvarFunction> {
+        match tcx.def_path_str(function).as_str() {
+            "std::sync::Condvar::new" => Some(CondvarFunction::New),
+            "std::sync::Condvar::wait" => Some(CondvarFunction::Wait),
+            "std::sync::Condvar::notify_one" => Some(CondvarFunction::NotifyOne),
+            "std::sync::Condvar::notify_all" => Some(CondvarFunction::NotifyAll),
+            _ => None,
+        }
+    }
+
+    /// `wait` unlocks the mutex of the guard and registers a waiter, after a notification
+    /// the thread locks the mutex again. The net cannot test if there are waiters,
+    /// so a notification may also be lost nondeterministically (`notify_one`) or
+    /// reach only some of the waiters (`notify_all`).
+    fn translate_condvar(
+        &mut self,
+        condvar_function: CondvarFunction,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) -> Result<()> {
+        let (place, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("condvar function without destination".into())
+        })?;
+        let local = function!(self).place_to_local(place)?;
+        if let CondvarFunction::New = condvar_function {
+            function!(self).call_transition(net!(self), "Condvar::new".into(), *continuation)?;
+            let condvar = self.condvar_list.add(net!(self))?;
+            debug!("link '{:?}' to condvar '{:?}'", local, condvar);
+            self.condvar_list.link(local, condvar);
+            return Ok(());
+        }
+        let condvar = args
+            .get(0)
+            .ok_or_else(|| TranslationError::UnexpectedMir("no condvar argument".into()))?;
+        let condvar = function!(self).op_to_local(condvar)?;
+        let condvar = self.condvar_for(condvar)?;
+        let waiters = condvar.waiters(&self.condvar_list);
+        let notified = condvar.notified(&self.condvar_list);
+        let name = format!("Condvar::{:?}", condvar_function);
+        match condvar_function {
+            CondvarFunction::Wait => {
+                let guard = args
+                    .get(1)
+                    .ok_or_else(|| TranslationError::UnexpectedMir("no condvar guard".into()))?;
+                let guard = function!(self).op_to_local(guard)?;
+                let mutex = match self.mutex_list.get_guard(guard).copied() {
+                    Some(mutex) => mutex,
+                    None => self.mutex_for(guard)?,
+                };
+                self.mutex_list.add_guard(local, mutex);
+                let source = function!(self).function_call_start_place()?;
+                let target = function!(self).get_basic_block_start(net!(self), *continuation)?;
+                let net = net!(self);
+                let locked = mutex.locked(&self.mutex_list);
+                let unlocked = mutex.unlocked(&self.mutex_list);
+                let wait = net.add_transition();
+                function!(self).name_transition(net, wait, name)?;
+                net.add_arc(source, wait)?;
+                net.add_arc(locked, wait)?;
+                net.add_arc(wait, unlocked)?;
+                net.add_arc(wait, waiters)?;
+                let waiting = net.add_place();
+                net.add_arc(wait, waiting)?;
+                let wake = net.add_transition();
+                function!(self).name_transition(net, wake, "Condvar::wake".into())?;
+                net.add_arc(waiting, wake)?;
+                net.add_arc(notified, wake)?;
+                net.add_arc(unlocked, wake)?;
+                net.add_arc(wake, locked)?;
+                net.add_arc(wake, target)?;
+            }
+            CondvarFunction::NotifyOne => {
+                let notify = function!(self).call_transition(net!(self), name, *continuation)?;
+                let net = net!(self);
+                net.add_arc(waiters, notify)?;
+                net.add_arc(notify, notified)?;
+                // nobody waits (or the notification is not observed)
+                function!(self).call_transition(
+                    net!(self),
+                    "Condvar::NotifyOne lost".into(),
+                    *continuation,
+                )?;
+            }
+            CondvarFunction::NotifyAll => {
+                // moves waiters one by one until it stops
+                let notifying = net!(self).add_place();
+                function!(self).branch(net!(self), notifying)?;
+                let target = function!(self).get_basic_block_start(net!(self), *continuation)?;
+                let net = net!(self);
+                let notify = net.add_transition();
+                function!(self).name_transition(net, notify, name)?;
+                net.add_arc(notifying, notify)?;
+                net.add_arc(waiters, notify)?;
+                net.add_arc(notify, notified)?;
+                net.add_arc(notify, notifying)?;
+                let done = net.add_transition();
+                function!(self).name_transition(net, done, "Condvar::NotifyAll done".into())?;
+                net.add_arc(notifying, done)?;
+                net.add_arc(done, target)?;
+            }
+            // handled above
+            CondvarFunction::New => {
+                return Err(TranslationError::UnexpectedMir(
+                    "Condvar::new is not a wait or notify operation".into(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    // The condvar that is linked to the local.
+    // If the identity cannot be resolved a new condvar is assumed for the local.
+    fn condvar_for(&mut self, local: Local) -> Result<CondvarRef> {
+        match self.condvar_list.get_linked(local) {
+            Some(condvar) => Ok(*condvar),
+            None => {
+                warn!(
+                    "cannot resolve the condvar of '{:?}', using a new condvar instead",
+                    local
+                );
+                let condvar = self.condvar_list.add(&mut self.net)?;
+                self.condvar_list.link(local, condvar);
+                Ok(condvar)
+            }
+        }
+    }
+
+    fn once_function(tcx: TyCtxt<'_>, function: DefId) -> Option<OnceFunction> {
+        match tcx.def_path_str(function) {
+            name if name == "std::sync::Once::new"
+                || name.contains("once_cell::sync::OnceCell::<T>::new") =>
+            {
+                Some(OnceFunction::New)
+            }
+            name if name == "std::sync::Once::call_once"
+                || name.contains("once_cell::sync::OnceCell::<T>::get_or_init") =>
+            {
+                Some(OnceFunction::CallOnce)
+            }
+            _ => None,
+        }
+    }
+
+    /// A `Once` (or `OnceCell`) is uninitialized until the closure of the first caller ran.
+    /// The first caller takes the uninitialized token and runs the closure,
+    /// callers in the meantime wait and all later callers skip the closure.
+    fn translate_once(
+        &mut self,
+        once_function: OnceFunction,
+        substs: SubstsRef<'tcx>,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+        cleanup: Option<BasicBlock>,
+    ) -> Result<()> {
+        let (place, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("once function without destination".into())
+        })?;
+        let local = function!(self).place_to_local(place)?;
+        if let OnceFunction::New = once_function {
+            function!(self).call_transition(net!(self), "Once::new".into(), *continuation)?;
+            let once = self.once_list.add(net!(self))?;
+            debug!("link '{:?}' to once '{:?}'", local, once);
+            self.once_list.link(local, once);
+            return Ok(());
+        }
+        let once = args
+            .get(0)
+            .ok_or_else(|| TranslationError::UnexpectedMir("no once argument".into()))?;
+        let once = function!(self).op_to_local(once)?;
+        let once = self.once_for(once)?;
+        // call_once<F>(&self, f: F) and get_or_init<F>(&self, f: F)
+        let closure = substs
+            .types()
+            .last()
+            .ok_or_else(|| TranslationError::UnexpectedMir("no once closure".into()))?;
+        let stack_top = function!(self); // needed in the closure
+        let (body, body_substs, body_args) = match closure.kind {
+            // the closure environment is the first argument of the closure body
+            ty::Closure(def_id, closure_substs) => (
+                def_id,
+                closure_substs,
+                args.iter()
+                    .skip(1)
+                    .map(|operand| stack_top.op_to_local(operand))
+                    .collect::<Result<_>>()?,
+            ),
+            ty::FnDef(def_id, fn_substs) => (def_id, fn_substs, Vec::new()),
+            ref kind => {
+                return Err(TranslationError::UnexpectedMir(format!(
+                    "cannot initialize once with {:?}",
+                    kind
+                )))
+            }
+        };
+        let source = function!(self).function_call_start_place()?;
+        let target = function!(self).get_basic_block_start(net!(self), *continuation)?;
+        let unwind = function!(self).unwind_target(net!(self), cleanup)?;
+        let uninitialized = once.uninitialized(&self.once_list);
+        let initialized = once.initialized(&self.once_list);
+        let net = net!(self);
+        let skip = net.add_transition();
+        function!(self).name_transition(net, skip, "Once skip".into())?;
+        net.add_arc(source, skip)?;
+        net.add_arc(initialized, skip)?;
+        net.add_arc(skip, initialized)?;
+        net.add_arc(skip, target)?;
+        let init = net.add_transition();
+        function!(self).name_transition(net, init, "Once init".into())?;
+        let init_start = net.add_place();
+        let init_end = net.add_place();
+        net.add_arc(source, init)?;
+        net.add_arc(uninitialized, init)?;
+        net.add_arc(init, init_start)?;
+        let done = net.add_transition();
+        function!(self).name_transition(net, done, "Once initialized".into())?;
+        net.add_arc(init_end, done)?;
+        net.add_arc(done, initialized)?;
+        net.add_arc(done, target)?;
+        let data_return = Local::new(net, "once_return")?;
+        self.translate(
+            body,
+            body_substs,
+            body_args,
+            data_return,
+            init_start,
+            init_end,
+            unwind,
+        )
+    }
+
+    // The once that is linked to the local.
+    // If the identity cannot be resolved a new (uninitialized) once is assumed for the local.
+    fn once_for(&mut self, local: Local) -> Result<OnceRef> {
+        match self.once_list.get_linked(local) {
+            Some(once) => Ok(*once),
+            None => {
+                warn!(
+                    "cannot resolve the once of '{:?}', using a new once instead",
+                    local
+                );
+                let once = self.once_list.add(&mut self.net)?;
+                self.once_list.link(local, once);
+                Ok(once)
+            }
+        }
+    }
+
+    fn channel_function(tcx: TyCtxt<'_>, function: DefId) -> Option<ChannelFunction> {
+        match tcx.def_path_str(function) {
+            name if name.contains("std::sync::mpsc::channel") => Some(ChannelFunction::Channel),
+            name if name.contains("std::sync::mpsc::sync_channel") => {
+                Some(ChannelFunction::SyncChannel)
+            }
+            name if name.contains("std::sync::mpsc::Sender::<T>::send")
+                | name.contains("std::sync::mpsc::SyncSender::<T>::send") =>
+            {
+                Some(ChannelFunction::Send)
+            }
+            name if name.contains("std::sync::mpsc::Receiver::<T>::recv") => {
+                Some(ChannelFunction::Recv)
+            }
+            name if name.contains("<std::sync::mpsc::Sender<T> as std::clone::Clone>::clone")
+                | name.contains("<std::sync::mpsc::SyncSender<T> as std::clone::Clone>::clone") =>
+            {
+                Some(ChannelFunction::Clone)
+            }
+            _ => None,
+        }
+    }
+
+    /// A channel is a buffer place that holds one token per sent message.
+    /// `recv` blocks on an empty buffer and `send` on a `sync_channel` blocks if no slot is free.
+    /// A cloned sender sends into the same buffer (a receiver that is moved into a thread
+    /// keeps its channel through the links of the closure).
+    /// The senders are counted, the channel is disconnected when the last one is dropped
+    /// (see `translate_sender_drop`).
+    fn translate_channel(
+        &mut self,
+        channel_function: ChannelFunction,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) -> Result<()> {
+        let (place, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("channel function without destination".into())
+        })?;
+        let name = format!("{:?}", channel_function);
+        let t = function!(self).call_transition(net!(self), name, *continuation)?;
+        match channel_function {
+            ChannelFunction::Channel | ChannelFunction::SyncChannel => {
+                let capacity = match channel_function {
+                    ChannelFunction::SyncChannel => {
+                        let capacity = match args.get(0) {
+                            Some(Operand::Constant(constant)) => constant
+                                .literal
+                                .try_eval_usize(self.tcx, ty::ParamEnv::reveal_all()),
+                            _ => None,
+                        };
+                        match capacity {
+                            // a rendezvous channel still needs one slot to hand over a message
+                            Some(0) => Some(1),
+                            None => {
+                                warn!(
+                                    "unknown capacity of sync channel, it is treated as unbounded"
+                                );
+                                None
+                            }
+                            capacity => capacity,
+                        }
+                    }
+                    _ => None,
+                };
+                let channel = self.channel_list.add(net!(self), capacity)?;
+                net!(self).add_arc(t, channel.senders(&self.channel_list))?;
+                let local = function!(self).place_to_local(place)?;
+                debug!("link '{:?}' to channel '{:?}'", local, channel);
+                self.channel_list.link(local, channel);
+            }
+            ChannelFunction::Send => {
+                let sender = args
+                    .get(0)
+                    .ok_or_else(|| TranslationError::UnexpectedMir("no sender".into()))?;
+                let sender = function!(self).op_to_local(sender)?;
+                let channel = self.channel_for(sender)?;
+                let net = net!(self);
+                net.add_arc(t, channel.buffer(&self.channel_list))?;
+                if let Some(free) = channel.free(&self.channel_list) {
+                    net.add_arc(free, t)?;
+                }
+            }
+            ChannelFunction::Recv => {
+                let receiver = args
+                    .get(0)
+                    .ok_or_else(|| TranslationError::UnexpectedMir("no receiver".into()))?;
+                let receiver = function!(self).op_to_local(receiver)?;
+                let channel = self.channel_for(receiver)?;
+                let net = net!(self);
+                net.add_arc(channel.buffer(&self.channel_list), t)?;
+                if let Some(free) = channel.free(&self.channel_list) {
+                    net.add_arc(t, free)?;
+                }
+                // without senders `recv` returns an `Err` (the buffer cannot be tested for
+                // emptiness, so this is possible even with messages left)
+                let t_disconnected = function!(self).call_transition(
+                    net,
+                    "Recv disconnected".into(),
+                    *continuation,
+                )?;
+                let disconnected = channel.disconnected(&self.channel_list);
+                net.add_arc(disconnected, t_disconnected)?;
+                net.add_arc(t_disconnected, disconnected)?;
+            }
+            ChannelFunction::Clone => {
+                let sender = args
+                    .get(0)
+                    .ok_or_else(|| TranslationError::UnexpectedMir("no sender".into()))?;
+                let sender = function!(self).op_to_local(sender)?;
+                let channel = self.channel_for(sender)?;
+                net!(self).add_arc(t, channel.senders(&self.channel_list))?;
+                let local = function!(self).place_to_local(place)?;
+                debug!("link '{:?}' to channel '{:?}'", local, channel);
+                self.channel_list.link(local, channel);
+            }
+        }
+        Ok(())
+    }
+
+    // The channels whose senders are dropped with a value of the type.
+    // That is a sender itself or the senders that are moved into a closure.
+    fn dropped_senders(&self, ty: Ty<'tcx>, local: Local) -> Vec<ChannelRef> {
+        match ty.kind {
+            ty::Adt(adt, _) => match self.tcx.def_path_str(adt.did).as_str() {
+                "std::sync::mpsc::Sender" | "std::sync::mpsc::SyncSender" => self
+                    .channel_list
+                    .get_linked(local)
+                    .copied()
+                    .into_iter()
+                    .collect(),
+                _ => Vec::new(),
+            },
+            ty::Closure(def_id, substs) => substs
+                .as_closure()
+                .upvar_tys(def_id, self.tcx)
+                .enumerate()
+                .filter_map(|(index, upvar_ty)| {
+                    let upvar = self.upvars.get(&(local, index))?;
+                    Some(self.dropped_senders(upvar_ty, *upvar))
+                })
+                .flatten()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Dropping a sender removes it from the count of its channel. Like for an `Arc` the
+    // count cannot be tested for zero, so the drop of the last sender (that disconnects
+    // the channel) is a nondeterministic alternative to the plain drop.
+    fn translate_sender_drop(
+        &mut self,
+        channels: &[ChannelRef],
+        drop: NodeRef,
+        target: BasicBlock,
+    ) -> Result<()> {
+        for channel in channels {
+            net!(self).add_arc(channel.senders(&self.channel_list), drop)?;
+        }
+        for channel in channels {
+            let last = function!(self).drop(net!(self), target, None)?;
+            let net = net!(self);
+            function!(self).rename_transition(net, last, "drop last Sender".into())?;
+            for other in channels {
+                net.add_arc(other.senders(&self.channel_list), last)?;
+            }
+            net.add_arc(channel.connected(&self.channel_list), last)?;
+            net.add_arc(last, channel.disconnected(&self.channel_list))?;
+        }
+        Ok(())
+    }
+
+    // The channel that is linked to the local.
+    // If the identity cannot be resolved a new channel is assumed for the local.
+    fn channel_for(&mut self, local: Local) -> Result<ChannelRef> {
+        match self.channel_list.get_linked(local) {
+            Some(channel) => Ok(*channel),
+            None => {
+                warn!(
+                    "cannot resolve the channel of '{:?}', using a new channel instead",
+                    local
+                );
+                let channel = self.channel_list.add_referenced(&mut self.net)?;
+                self.channel_list.link(local, channel);
+                Ok(channel)
+            }
+        }
+    }
+
+    fn is_thread_spawn(tcx: TyCtxt<'_>, function: DefId) -> bool {
+        tcx.def_path_str(function).contains("std::thread::spawn")
+    }
+
+    /// Forks the control flow: one token continues in the caller and one starts the body
+    /// of the spawned closure (or function). The end place of the thread is remembered
+    /// with the join handle.
+    fn translate_spawn(
+        &mut self,
+        substs: SubstsRef<'tcx>,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) -> Result<()> {
+        // spawn::<F, T>(f: F)
+        let stack_top = function!(self); // needed in the closure
+        let (body, body_substs, body_args) = match substs.type_at(0).kind {
+            // the closure environment is the first argument of the closure body
+            ty::Closure(def_id, closure_substs) => (
+                def_id,
+                closure_substs,
+                args.iter()
+                    .map(|operand| stack_top.op_to_local(operand))
+                    .collect::<Result<_>>()?,
+            ),
+            ty::FnDef(def_id, fn_substs) => (def_id, fn_substs, Vec::new()),
+            // a future of an executor, e.g. the generator of an async block in a `GenFuture`
+            ref kind => match substs.type_at(0).walk().find_map(|ty| match ty.kind {
+                ty::Generator(def_id, generator_substs, _) => Some((def_id, generator_substs)),
+                _ => None,
+            }) {
+                Some((def_id, generator_substs)) => (
+                    def_id,
+                    generator_substs,
+                    args.iter()
+                        .map(|operand| stack_top.op_to_local(operand))
+                        .collect::<Result<_>>()?,
+                ),
+                None => {
+                    return Err(TranslationError::UnexpectedMir(format!(
+                        "cannot spawn a thread with {:?}",
+                        kind
+                    )))
+                }
+            },
         };
-        let data_return = Local::new(net!(self), "main_return")?;
+        let (handle_place, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("thread spawn without destination".into())
+        })?;
+        let handle = function!(self).place_to_local(handle_place)?;
+        let loop_span = {
+            let function = function!(self);
+            match function.active_block {
+                Some(block) if Self::on_cycle(function.mir_body, block) => {
+                    Some(function.mir_body[block].terminator().source_info.span)
+                }
+                _ => None,
+            }
+        };
+        if let Some(span) = loop_span {
+            let location = self.tcx.sess.source_map().span_to_string(span);
+            if self.options.one_safe {
+                return Err(TranslationError::NotOneSafe(format!(
+                    "thread spawned in a loop at {}",
+                    location
+                )));
+            }
+            // every iteration forks another token into the same subnet and join handle
+            warn!(
+                "thread spawned in a loop at {}: all threads of the loop share one subnet, \
+                 the net does not model them as independent threads",
+                location
+            );
+        }
+        let net = net!(self);
+        let thread_start = net.add_place();
+        let thread_end = net.add_place();
+        net.name(thread_end, format!("{} end", self.tcx.def_path_str(body)))?;
+        function!(self).fork(net, *continuation, thread_start)?;
+        self.threads.insert(handle, thread_end);
+        let data_return = Local::new(net, "thread_return")?;
+        info!("spawning thread {:?}", body);
+        // a panic ends the thread but for now it is treated like any other panic
+        let unwind_place = self.unwind_abort_place;
         self.translate(
-            main_fn,
-            Vec::new(), //TODO: Arguments would be important for HiLvl Nets
+            body,
+            body_substs,
+            body_args,
             data_return,
-            start_place,
-            self.program_end_place
-                .expect("no program end place defined"),
-        )?;
-        Ok(&self.net)
+            thread_start,
+            thread_end,
+            unwind_place,
+        )
     }
 
-    fn translate<'a>(
+    // Whether the block can be reached again from its successors, e.g. in a loop
+    fn on_cycle(body: &Body<'_>, block: BasicBlock) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<BasicBlock> = body[block].terminator().successors().cloned().collect();
+        while let Some(next) = stack.pop() {
+            if next == block {
+                return true;
+            }
+            if visited.insert(next) {
+                stack.extend(body[next].terminator().successors().cloned());
+            }
+        }
+        false
+    }
+
+    fn is_thread_join(tcx: TyCtxt<'_>, function: DefId) -> bool {
+        tcx.def_path_str(function)
+            .contains("std::thread::JoinHandle::<T>::join")
+    }
+
+    /// Waits for the end place of the joined thread.
+    /// If the join handle is unknown the join does not synchronize.
+    fn translate_join(
         &mut self,
-        function: DefId,
-        args: Vec<Local>,
-        data_return: Local,
-        start_place: NodeRef,
-        return_flow: NodeRef,
+        args: &Vec<Operand<'tcx>>,
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
     ) -> Result<()> {
-        let fn_name = self.tcx.def_path_str(function);
-        start_place.name(&mut self.net, fn_name.clone())?;
-        if Self::is_unique(&fn_name) {
-            self.translate_unique(
-                function,
-                args,
-                data_return,
-                start_place,
-                return_flow,
-                fn_name,
-            )?
-        } else {
-            self.translate_default(
-                function,
-                args,
-                data_return,
-                start_place,
-                return_flow,
-                fn_name,
-            )?
+        let handle = args
+            .get(0)
+            .ok_or_else(|| TranslationError::UnexpectedMir("no join handle arg found".into()))?;
+        let handle = function!(self).op_to_local(handle)?;
+        let (_, continuation) = destination.as_ref().ok_or_else(|| {
+            TranslationError::UnexpectedMir("thread join without destination".into())
+        })?;
+        let thread_end = self.threads.get(&handle).copied();
+        if thread_end.is_none() {
+            warn!("joined unknown thread handle {:?}", handle);
         }
+        if !self.joined.insert(handle) {
+            // MIR does not allow a double join but a join in a loop looks the same
+            warn!("thread handle {:?} is joined more than once", handle);
+        }
+        function!(self).join(net!(self), *continuation, thread_end)?;
         Ok(())
     }
 
@@ -161,6 +1996,54 @@ impl<'tcx> Translator<'tcx> {
         }
     }
 
+    fn exceeds_depth(options: &Options, depth: usize) -> bool {
+        match options.max_depth {
+            Some(max_depth) => depth >= max_depth,
+            None => false,
+        }
+    }
+
+    fn is_box_allocation(tcx: TyCtxt<'_>, function: DefId) -> bool {
+        let lang_items = tcx.lang_items();
+        lang_items.exchange_malloc_fn() == Some(function)
+            || lang_items.box_free_fn() == Some(function)
+            || tcx.def_path_str(function) == "std::boxed::Box::<T>::new"
+    }
+
+    fn is_scheduling_point(tcx: TyCtxt<'_>, function: DefId) -> bool {
+        match tcx.def_path_str(function).as_str() {
+            "std::thread::sleep" | "std::thread::yield_now" => true,
+            _ => false,
+        }
+    }
+
+    // functions that end the whole program instead of only the calling thread
+    fn is_process_exit(tcx: TyCtxt<'_>, function: DefId) -> bool {
+        match tcx.def_path_str(function).as_str() {
+            "std::process::exit" | "std::process::abort" => true,
+            _ => false,
+        }
+    }
+
+    // Functions that are modeled as opaque transitions (`--treat-atomic` and `--exclude`)
+    // unless they are forced to be inlined (`--force-inline` wins).
+    fn skip_function(options: &Options, name: &str) -> bool {
+        !Self::matches_path(&options.force_inline, name)
+            && (Self::matches_path(&options.atomic, name)
+                || Self::matches_path(&options.exclude, name))
+    }
+
+    // A function matches its own path and the path of every module that contains it
+    // (`log` and `log::` both match `log::info`).
+    fn matches_path(paths: &[String], name: &str) -> bool {
+        paths
+            .iter()
+            .map(|path| path.trim_end_matches("::"))
+            .any(|path| {
+                name == path || (name.starts_with(path) && name[path.len()..].starts_with("::"))
+            })
+    }
+
     pub fn is_unique(name: &str) -> bool {
         match name {
             name if name.contains("std::sync::Mutex::<T>::new")
@@ -176,24 +2059,90 @@ impl<'tcx> Translator<'tcx> {
     fn translate_default(
         &mut self,
         function: DefId,
+        substs: SubstsRef<'tcx>,
         args: Vec<Local>,
         data_return: Local,
         start_place: NodeRef,
         return_flow: NodeRef,
+        unwind_flow: NodeRef,
         fn_name: String,
     ) -> Result<()> {
+        // every instance of a generic function is translated on its own
+        let instance = (function, substs);
+        if let Some(subnet) = self.subnets.get(&instance).cloned() {
+            if self.in_progress.contains(&instance) {
+                info!("recursive call of {:?}: back-edge to its start", fn_name);
+                // the recursive frames share the places of the subnet
+                if self.options.one_safe {
+                    return Err(TranslationError::NotOneSafe(format!(
+                        "recursive call of {}",
+                        fn_name
+                    )));
+                }
+            } else if self.options.one_safe {
+                // concurrent callers could meet in a shared subnet, so every call gets its own
+                info!("translating {:?} again for a 1-safe net", fn_name);
+                self.subnets.remove(&instance);
+                return self.translate_default(
+                    function,
+                    substs,
+                    args,
+                    data_return,
+                    start_place,
+                    return_flow,
+                    unwind_flow,
+                    fn_name,
+                );
+            } else if self.shares_resources(&subnet, &args, data_return) {
+                // the resources in the subnet are the ones of its first call
+                info!(
+                    "translating {:?} again for the resources of the call",
+                    fn_name
+                );
+                self.subnets.remove(&instance);
+                return self.translate_default(
+                    function,
+                    substs,
+                    args,
+                    data_return,
+                    start_place,
+                    return_flow,
+                    unwind_flow,
+                    fn_name,
+                );
+            } else {
+                info!("reusing the translation of {:?}", fn_name);
+            }
+            // the call counts to the nodes of the function (see `NetStats`)
+            let subnet_name = self.describe_as_module(function, substs);
+            self.net.enter(subnet_name.clone());
+            let connected = self
+                .connect_subnet(&subnet, &subnet_name, start_place, return_flow, unwind_flow)
+                .and_then(|call_site| {
+                    self.pass_locals(&subnet, &subnet_name, call_site, &args, data_return)
+                });
+            self.net.leave();
+            return connected;
+        }
+        if !self.tcx.is_mir_available(function) {
+            return Err(TranslationError::MirUnavailable(function));
+        }
         info!("\n\nENTERING function: {:?}", fn_name);
         if let Some(file) = &mut self.mir_dump {
-            if !self.visited.contains(&function) {
-                write_mir_pretty(self.tcx, Some(function), file).unwrap();
-            }
+            writeln!(file, "// {}", fn_name)
+                .and_then(|_| write_mir_pretty(self.tcx, Some(function), file))
+                .map_err(|err| TranslationError::MirDump(err.to_string()))?;
         };
-        self.visited.insert(function);
+        let subnet_name = self.describe_as_module(function, substs);
+        self.net.enter(subnet_name.clone());
+        let subnet = self.add_subnet(&subnet_name, &args, data_return)?;
+        self.subnets.insert(instance, subnet.clone());
+        self.in_progress.insert(instance);
+        self.connect_subnet(&subnet, &subnet_name, start_place, return_flow, unwind_flow)?;
         let body = self.tcx.optimized_mir(function);
         let (const_memory, mut static_memory) = if self.call_stack.is_empty() {
-            let constants = net!(self).add_place();
-            constants.name(net!(self), "CONSTANTS".into())?;
-            PlaceRef::try_from(constants)?.marking(&mut self.net, 1)?;
+            let constants = marked_place(net!(self))?;
+            net!(self).name(constants, "CONSTANTS".into())?;
             (Data::Constant(constants), std::collections::HashMap::new())
         } else {
             (
@@ -205,8 +2154,8 @@ impl<'tcx> Translator<'tcx> {
         for (promoted, _) in self.tcx.promoted_mir(function).iter_enumerated() {
             if static_memory.get(&promoted).is_none() {
                 let promoted_node = net!(self).add_place();
-                promoted_node.name(
-                    net!(self),
+                net!(self).name(
+                    promoted_node,
                     format!("Promoted_{} {}", promoted.index(), fn_name),
                 )?;
                 static_memory.insert(promoted, Data::Static(promoted_node));
@@ -214,26 +2163,216 @@ impl<'tcx> Translator<'tcx> {
                 warn!("duplicate of promoted static");
             }
         }
-        let petri_function = Function::new(
+        // statics are accessed by a path, so every function needs to know the shared places
+        let mut collector = StaticCollector(Vec::new());
+        collector.visit_body(body.unwrap_read_only());
+        for statik in collector.0 {
+            if !self.statics.contains_key(&statik) {
+                let place = marked_place(net!(self))?;
+                net!(self).name(place, self.tcx.def_path_str(statik))?;
+                self.statics.insert(statik, place);
+            }
+        }
+        let mut petri_function = Function::new(
             fn_name.clone(),
             body,
             net!(self),
             args,
             data_return,
-            start_place,
+            subnet.entry,
             &const_memory,
             &static_memory,
-            return_flow,
+            &self.statics,
+            subnet.exit,
+            subnet.unwind_exit,
             &mut self.mutex_list,
             self.tcx,
+            self.options.granularity,
         )?;
+        if self.options.node_map {
+            petri_function.record_nodes();
+        }
         self.call_stack.push(petri_function);
+        self.frame_substs.push(substs);
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.enter(&fn_name, self.call_stack.len());
+        }
         self.visit_body(body.unwrap_read_only());
-        self.call_stack.pop();
+        if let Some(mut function) = self.call_stack.pop() {
+            if self.options.debug_functions {
+                eprintln!("{}", function.dump());
+            }
+            self.node_map.extend(function.take_nodes());
+        }
+        self.frame_substs.pop();
+        self.net.leave();
+        self.in_progress.remove(&instance);
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.leave(self.call_stack.len());
+        }
         info!("\nLEAVING function: {:?}\n", fn_name);
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // The full path of a function instance that names the places of its subnet,
+    // e.g. `program::module::function::<u32>`
+    // (`def_path_str` omits the local crate and the generic arguments).
+    fn describe_as_module(&self, function: DefId, substs: SubstsRef<'tcx>) -> String {
+        let path = self.tcx.def_path_str_with_substs(function, substs);
+        if function.is_local() {
+            format!("{}::{}", self.name, path)
+        } else {
+            path
+        }
+    }
+
+    fn add_subnet(&mut self, fn_name: &str, args: &[Local], data_return: Local) -> Result<Subnet> {
+        let net = net!(self);
+        let entry = net.add_place();
+        net.name(entry, format!("{} entry", fn_name))?;
+        let exit = net.add_place();
+        net.name(exit, format!("{} exit", fn_name))?;
+        let unwind_exit = net.add_place();
+        net.name(unwind_exit, format!("{} unwind", fn_name))?;
+        Ok(Subnet {
+            entry,
+            exit,
+            unwind_exit,
+            args: args.to_vec(),
+            data_return,
+        })
+    }
+
+    // Names a transition through the calling function (see `Function::name_transition`).
+    // The call of the entry function has no caller, its transitions only get their name.
+    fn name_transition(&mut self, t: NodeRef, name: String) -> Result<()> {
+        match self.call_stack.peek_mut() {
+            Some(function) => function.name_transition(&mut self.net, t, name),
+            None => Ok(self.net.name(t, name)?),
+        }
+    }
+
+    // Connects a call site to the subnet of the called function.
+    // A subnet that is shared by several calls has one return edge per caller,
+    // the call marks a place of its call site so that only its own return edge is enabled.
+    // Returns the call site place.
+    fn connect_subnet(
+        &mut self,
+        subnet: &Subnet,
+        fn_name: &str,
+        start_place: NodeRef,
+        return_flow: NodeRef,
+        unwind_flow: NodeRef,
+    ) -> Result<NodeRef> {
+        let call_site = net!(self).add_place();
+        net!(self).name(call_site, format!("{} call site", fn_name))?;
+        let call = net!(self).add_transition();
+        self.name_transition(call, format!("call {}", fn_name))?;
+        let ret = net!(self).add_transition();
+        self.name_transition(ret, format!("return {}", fn_name))?;
+        let unwind = net!(self).add_transition();
+        self.name_transition(unwind, format!("unwind {}", fn_name))?;
+        let net = net!(self);
+        net.add_arc(start_place, call)?;
+        net.add_arc(call, subnet.entry)?;
+        net.add_arc(call, call_site)?;
+        net.add_arc(subnet.exit, ret)?;
+        net.add_arc(call_site, ret)?;
+        net.add_arc(ret, return_flow)?;
+        net.add_arc(subnet.unwind_exit, unwind)?;
+        net.add_arc(call_site, unwind)?;
+        net.add_arc(unwind, unwind_flow)?;
+        Ok(call_site)
+    }
+
+    // Connects the arguments and the return value of a later call to the locals of the
+    // shared subnet. Every pair is accessed by a transition that is only enabled while
+    // the call site is marked.
+    fn pass_locals(
+        &mut self,
+        subnet: &Subnet,
+        fn_name: &str,
+        call_site: NodeRef,
+        args: &[Local],
+        data_return: Local,
+    ) -> Result<()> {
+        let mut passed: Vec<(String, Local, Local)> = args
+            .iter()
+            .zip(&subnet.args)
+            .enumerate()
+            .map(|(index, (arg, shared))| {
+                (
+                    format!("pass argument {} to {}", index + 1, fn_name),
+                    *arg,
+                    *shared,
+                )
+            })
+            .collect();
+        passed.push((
+            format!("pass return value of {}", fn_name),
+            data_return,
+            subnet.data_return,
+        ));
+        for (name, local, shared) in passed {
+            // e.g. constants are the same place in every call
+            if local.live_place == shared.live_place {
+                continue;
+            }
+            let t = net!(self).add_transition();
+            self.name_transition(t, name)?;
+            let net = net!(self);
+            for place in &[call_site, local.live_place, shared.live_place] {
+                net.add_arc(*place, t)?;
+                net.add_arc(t, *place)?;
+            }
+        }
         Ok(())
     }
 
+    // Whether the call or the subnet passes a resource (e.g. a mutex or a thread handle).
+    // A shared subnet could only use the resources of its first call.
+    fn shares_resources(&self, subnet: &Subnet, args: &[Local], data_return: Local) -> bool {
+        args.iter()
+            .chain(&subnet.args)
+            .chain(&[data_return, subnet.data_return])
+            .any(|local| self.has_resource(*local))
+    }
+
+    fn has_resource(&self, local: Local) -> bool {
+        self.mutex_list.get_linked(local).is_some()
+            || self.channel_list.get_linked(local).is_some()
+            || self.arc_list.get_linked(local).is_some()
+            || self.rwlock_list.get_linked(local).is_some()
+            || self.atomic_list.get_linked(local).is_some()
+            || self.condvar_list.get_linked(local).is_some()
+            || self.once_list.get_linked(local).is_some()
+            || self.threads.contains_key(&local)
+            || self
+                .upvars
+                .keys()
+                .any(|(environment, _)| *environment == local)
+    }
+
+    // The mutex that is linked to the local.
+    // If the identity cannot be resolved a new (unlocked) mutex is assumed for the local.
+    fn mutex_for(&mut self, local: Local) -> Result<MutexRef> {
+        match self.mutex_list.get_linked(local) {
+            Some(mutex) => Ok(*mutex),
+            None => {
+                warn!(
+                    "cannot resolve the mutex of '{:?}', using a new mutex instead",
+                    local
+                );
+                let mutex = self.mutex_list.add_unlocked(&mut self.net)?;
+                self.mutex_list.link(local, mutex);
+                Ok(mutex)
+            }
+        }
+    }
+
     fn translate_unique(
         &mut self,
         function: DefId,
@@ -247,7 +2386,7 @@ impl<'tcx> Translator<'tcx> {
 
         // bridge the call
         let t = net.add_transition();
-        t.name(net, fn_name.clone())?;
+        function!(self).name_transition(net, t, fn_name.clone())?;
         net.add_arc(start_place, t)?;
         net.add_arc(t, return_flow)?;
 
@@ -256,126 +2395,269 @@ impl<'tcx> Translator<'tcx> {
                 let mutex = *self
                     .mutex_list
                     .get_linked(data_return)
-                    .expect("mutex not found");
+                    .ok_or_else(|| TranslationError::UnexpectedMir("mutex not found".into()))?;
                 net.add_arc(mutex.uninitialized(&self.mutex_list), t)?;
                 net.add_arc(t, mutex.unlocked(&self.mutex_list))?;
             }
             name if name.contains("std::sync::Mutex::<T>::lock") => {
-                let mutex = *self
-                    .mutex_list
-                    .get_linked(*args.get(0).expect("no mutex lock arg found"))
-                    .expect("mutex not found");
+                let mutex = *args.get(0).ok_or_else(|| {
+                    TranslationError::UnexpectedMir("no mutex lock arg found".into())
+                })?;
+                let mutex = self.mutex_for(mutex)?;
+                let net = &mut self.net;
+                self.mutex_list.add_guard(data_return, mutex);
+                net.add_arc(mutex.unlocked(&self.mutex_list), t)?;
+                net.add_arc(t, mutex.locked(&self.mutex_list))?;
+                // a poisoned mutex is locked as well but `lock` returns an `Err`.
+                // The healthy path stays enabled (the net has no inhibitor arcs),
+                // the poisoned one can only fire after a guard was dropped in a panic.
+                let t_poisoned = net.add_transition();
+                function!(self).name_transition(net, t_poisoned, format!("{} poisoned", name))?;
+                net.add_arc(start_place, t_poisoned)?;
+                net.add_arc(t_poisoned, return_flow)?;
+                net.add_arc(mutex.unlocked(&self.mutex_list), t_poisoned)?;
+                net.add_arc(t_poisoned, mutex.locked(&self.mutex_list))?;
+                net.add_arc(mutex.poisoned(&self.mutex_list), t_poisoned)?;
+                net.add_arc(t_poisoned, mutex.poisoned(&self.mutex_list))?;
+            }
+            // try_lock does not block, the bridge takes the unlocked mutex
+            // and the failing path can only fire while the mutex is locked.
+            // Both continue with the returned Result, the match on it is not
+            // bound to the outcome.
+            name if name.contains("std::sync::Mutex::<T>::try_lock") => {
+                let mutex = *args.get(0).ok_or_else(|| {
+                    TranslationError::UnexpectedMir("no mutex try_lock arg found".into())
+                })?;
+                let mutex = self.mutex_for(mutex)?;
+                let net = &mut self.net;
                 self.mutex_list.add_guard(data_return, mutex);
                 net.add_arc(mutex.unlocked(&self.mutex_list), t)?;
                 net.add_arc(t, mutex.locked(&self.mutex_list))?;
+                let t_failed = net.add_transition();
+                function!(self).name_transition(net, t_failed, format!("{} failed", name))?;
+                net.add_arc(start_place, t_failed)?;
+                net.add_arc(t_failed, return_flow)?;
+                net.add_arc(mutex.locked(&self.mutex_list), t_failed)?;
+                net.add_arc(t_failed, mutex.locked(&self.mutex_list))?;
+            }
+            name => {
+                return Err(TranslationError::UnexpectedMir(format!(
+                    "unhandled unique function {}",
+                    name
+                )))
             }
-            name if name.contains("std::sync::Mutex::<T>::try_lock") => unimplemented!(),
-            _ => panic!("unhandled unique function"),
         };
         Ok(())
     }
-}
 
-impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
-    fn visit_body(&mut self, body: ReadOnlyBodyAndCache<'_, 'tcx>) {
-        match body.phase {
-            MirPhase::Optimized => {}
-            _ => error!("tried to translate unoptimized MIR"),
+    fn activate_block(&mut self, block: BasicBlock) -> Result<()> {
+        function!(self).activate_block(net!(self), block)?;
+        Ok(())
+    }
+
+    fn set_source_info(&mut self, source_info: &SourceInfo) -> Result<()> {
+        if self.options.annotate_source || self.options.node_map {
+            let location = self.tcx.sess.source_map().span_to_string(source_info.span);
+            if self.options.node_map {
+                function!(self).set_span(location.clone());
+            }
+            if self.options.annotate_source {
+                function!(self).set_source_location(Some(location));
+            }
         }
-        self.super_body(body);
+        Ok(())
     }
 
-    fn visit_basic_block_data(&mut self, block: BasicBlock, data: &BasicBlockData<'tcx>) {
-        trace!("---BasicBlock {:?}---", block);
-        function!(self)
-            .activate_block(net!(self), block)
-            .expect("unable to activate basic");
-        self.super_basic_block_data(block, data)
+    // The destination of a call shares the resources of the arguments
+    // (e.g. the guard that is returned by `lock` or a value that is passed through)
+    fn link_call_arguments(
+        &mut self,
+        args: &[Operand<'tcx>],
+        destination: &Option<(Place<'tcx>, BasicBlock)>,
+    ) -> Result<()> {
+        for arg in args {
+            let local = Self::operand_link_source(function!(self), &self.upvars, arg)?;
+            if let Some((place, _)) = destination {
+                let destination = function!(self).place_to_local(place)?;
+                self.propagate_links(local, destination);
+            }
+        }
+        Ok(())
     }
 
-    fn visit_assign(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>, location: Location) {
+    fn translate_assign(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>) -> Result<()> {
+        // remember reified functions to resolve calls through function pointers
+        if let Rvalue::Cast(_, Operand::Constant(constant), _) = rvalue {
+            if let ty::FnDef(def_id, _) = constant.literal.ty.kind {
+                if let (PlaceBase::Local(local), true) = (&place.base, place.projection.is_empty())
+                {
+                    function!(self).add_fn_pointer(*local, def_id);
+                }
+                if !self.address_taken.contains(&def_id) {
+                    self.address_taken.push(def_id);
+                }
+            }
+        }
+        // the overflow flag of the result is checked by the following Assert
+        if let Rvalue::CheckedBinaryOp(..) = rvalue {
+            if let (PlaceBase::Local(local), true) = (&place.base, place.projection.is_empty()) {
+                function!(self).add_checked_result(*local);
+            }
+        }
+        // an atomic or once static is shared by all references to it
+        if let Rvalue::Ref(_, _, source) = rvalue {
+            if let PlaceBase::Static(box Static {
+                kind: StaticKind::Static,
+                ty,
+                ..
+            }) = &source.base
+            {
+                let path = match ty.ty_adt_def() {
+                    Some(adt) => self.tcx.def_path_str(adt.did),
+                    None => String::new(),
+                };
+                let statik = function!(self).place_to_local(source)?;
+                if path.starts_with("std::sync::atomic::Atomic")
+                    && self.atomic_list.get_linked(statik).is_none()
+                {
+                    let atomic = self.atomic_list.add(net!(self));
+                    if let Some(atomic) = self.record(atomic) {
+                        self.atomic_list.link(statik, atomic);
+                    }
+                }
+                if (path == "std::sync::Once" || path == "once_cell::sync::OnceCell")
+                    && self.once_list.get_linked(statik).is_none()
+                {
+                    let once = self.once_list.add(net!(self));
+                    if let Some(once) = self.record(once) {
+                        self.once_list.link(statik, once);
+                    }
+                }
+            }
+        }
+        self.track_known_value(place, rvalue)?;
+        // the captured upvars of a closure are linked on their own
+        if let Rvalue::Aggregate(box AggregateKind::Closure(..), upvars) = rvalue {
+            let environment = function!(self).place_to_local(place)?;
+            for (index, upvar) in upvars.iter().enumerate() {
+                let upvar = function!(self).op_to_local(upvar)?;
+                self.upvars.insert((environment, index), upvar);
+            }
+        }
+        let upvars = &self.upvars;
         let function = function!(self);
 
         let mut locals = Vec::new();
         match rvalue {
-            Rvalue::Use(operand) | Rvalue::Repeat(operand, _) | Rvalue::Cast(_, operand, _) => {
-                locals.push(function.op_to_local(operand))
+            Rvalue::Use(operand) | Rvalue::Repeat(operand, _) => {
+                locals.push(Self::operand_link_source(function, upvars, operand)?)
+            }
+            // pointer casts and unsizing keep the pointee, numeric casts produce a new value
+            Rvalue::Cast(kind, operand, ty) => {
+                let keeps_identity = match kind {
+                    CastKind::Pointer(_) => true,
+                    CastKind::Misc => ty.is_region_ptr() || ty.is_unsafe_ptr() || ty.is_box(),
+                };
+                if keeps_identity {
+                    locals.push(Self::operand_link_source(function, upvars, operand)?)
+                }
+            }
+            Rvalue::Ref(_, _, source) => locals.push(Self::link_source(function, upvars, source)?),
+            Rvalue::Discriminant(source) => locals.push(function.place_to_local(source)?),
+            Rvalue::AddressOf(_, source) => {
+                locals.push(Self::link_source(function, upvars, source)?)
+            }
+            // e.g. a struct holds its fields and the closure environment the captured upvars
+            Rvalue::Aggregate(_, operands) => {
+                for operand in operands {
+                    locals.push(Self::operand_link_source(function, upvars, operand)?)
+                }
             }
-            Rvalue::Ref(_, _, place) => locals.push(function.place_to_local(place)),
-            Rvalue::Discriminant(place) => locals.push(function.place_to_local(place)),
-            Rvalue::AddressOf(_, _) => locals.push(function.place_to_local(place)),
-            Rvalue::Aggregate(_, _) => unimplemented!(),
             _ => {}
         }
 
+        let destination = function.place_to_local(place)?;
         for local in locals {
-            if let Some(mutex) = self.mutex_list.is_linked(local) {
-                debug!("link '{:?}' to mutex '{:?}'", place, mutex);
-                self.mutex_list.link(function.place_to_local(place), *mutex)
-            }
+            self.propagate_links(local, destination);
         }
-        self.super_assign(place, rvalue, location);
-    }
-
-    fn visit_statement(&mut self, statement: &Statement<'tcx>, location: Location) {
-        trace!("{:?}: ", statement.kind);
-        function!(self)
-            .add_statement(net!(self), statement)
-            .expect("unable to add statement");
-        self.super_statement(statement, location);
+        Ok(())
     }
 
-    fn visit_terminator_kind(&mut self, kind: &TerminatorKind<'tcx>, location: Location) {
-        trace!("{:?}", kind);
-
-        // check mutex links
-        match kind {
-            Call {
-                func: _,
-                ref args,
-                ref destination,
-                ..
-            } => {
-                for arg in args {
-                    let local = function!(self).op_to_local(arg);
-                    if let Some((place, _)) = destination {
-                        if let Some(mutex) = self.mutex_list.is_linked(local) {
-                            debug!("link '{:?}' to mutex '{:?}'", place, mutex);
-                            self.mutex_list
-                                .link(function!(self).place_to_local(place), *mutex)
-                        }
-                    }
-                }
+    fn translate_statement(&mut self, statement: &Statement<'tcx>) -> Result<()> {
+        match &statement.kind {
+            // the effects of the assembly are unknown, it stays visible as an opaque transition
+            StatementKind::InlineAsm(..) => {
+                trace!(
+                    "inline asm at {}",
+                    self.tcx
+                        .sess
+                        .source_map()
+                        .span_to_string(statement.source_info.span)
+                );
+                self.unsupported("InlineAsm", statement.source_info);
+                function!(self).add_opaque_statement(net!(self), statement, "InlineAsm")?;
+                return Ok(());
+            }
+            // the variant is tracked independent of the granularity
+            StatementKind::SetDiscriminant {
+                place,
+                variant_index,
+            } => function!(self).set_variant(place, Some(variant_index.as_usize())),
+            _ => {}
+        }
+        // a coarse granularity folds the statements without side effects
+        function!(self).add_statement(net!(self), statement)?;
+        match &statement.kind {
+            StatementKind::StorageLive(local) => function!(self).storage_live(net!(self), local)?,
+            StatementKind::StorageDead(local) => {
+                function!(self).storage_dead(net!(self), local)?;
+            }
+            StatementKind::SetDiscriminant { place, .. } => {
+                function!(self).set_discriminant(net!(self), place)?
             }
             _ => {}
         }
+        Ok(())
+    }
 
+    fn translate_terminator(
+        &mut self,
+        kind: &TerminatorKind<'tcx>,
+        location: Location,
+    ) -> Result<()> {
         use rustc::mir::TerminatorKind::*;
         let net = net!(self);
-        function!(self)
-            .finish_basic_block(net)
-            .expect("cannot end statement block");
+        function!(self).finish_basic_block(net)?;
         match kind {
             Return => {
                 // trace!("Return");
-                function!(self).retorn(net).expect("return failed");
+                function!(self).retorn(net)?;
             }
 
             Goto { target } => {
                 // trace!("Goto");
-                function!(self)
-                    .goto(net, *target)
-                    .expect("Goto Block failed");
+                function!(self).goto(net, *target)?;
             }
 
             SwitchInt {
-                discr: _,
+                discr,
                 switch_ty: _,
-                values: _,
+                values,
                 targets,
-            } => function!(self)
-                .switch_int(net, targets)
-                .expect("switch int failed"),
+            } => {
+                // only one branch is feasible if the discriminant is known in this block
+                let value = match discr {
+                    Operand::Constant(constant) => self.constant_bits(constant),
+                    _ => function!(self).known_value(discr),
+                };
+                let known = value.map(|value| {
+                    values
+                        .iter()
+                        .position(|candidate| *candidate == value)
+                        .unwrap_or(values.len())
+                });
+                function!(self).switch_int(net, targets, known)?
+            }
 
             Call {
                 ref func,
@@ -391,111 +2673,193 @@ impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
                 let sty = {
                     match func {
                         Operand::Copy(ref place) | Operand::Move(ref place) => {
-                            let function = self.call_stack.peek().expect("peeked empty stack");
+                            let function = self
+                                .call_stack
+                                .peek()
+                                .ok_or(TranslationError::EmptyCallStack)?;
                             let decls = function.mir_body.local_decls();
                             let place_ty: &mir::tcx::PlaceTy<'tcx> = &place.base.ty(decls);
                             place_ty.ty
                         }
-                        Operand::Constant(ref constant) => &constant.literal.ty,
+                        Operand::Constant(ref constant) => constant.literal.ty,
                     }
                 };
-                let function = match sty.kind {
+                let sty = Self::monomorphize(self.tcx, &self.frame_substs, sty);
+                match sty.kind {
+                    ty::FnDef(function, substs) => {
+                        self.translate_call(function, substs, args, destination, *cleanup)?
+                    }
                     ty::FnPtr(_) => {
-                        error!("Function pointers are not supported");
-                        panic!("")
+                        self.translate_fn_pointer_call(func, args, destination, *cleanup)?
                     }
-                    ty::FnDef(def_id, _) => def_id,
                     _ => {
                         error!("Expected function definition or pointer but got: {:?}", sty);
-                        panic!("")
-                    }
-                };
-                if !Self::is_panic(self.tcx, function) {
-                    if self.tcx.is_foreign_item(function) || !self.tcx.is_mir_available(function) {
-                        info!("emulating mir-less item {:?}", function);
-                        function!(self)
-                            .emulate_foreign(
-                                net,
-                                &self.tcx.def_path_str(function),
-                                args,
-                                destination,
-                                *cleanup,
-                                self.unwind_abort_place,
-                            )
-                            .expect("unknown foreign item");
-                    } else {
-                        let start_place = function!(self)
-                            .function_call_start_place()
-                            .expect("Unable to infer start place of function call")
-                            .clone();
-                        let (return_place, return_block) = destination.as_ref().expect(&format!(
-                            "diverging function: {}",
-                            self.tcx.def_path_str(function),
-                        ));
-                        let data_return = *function!(self)
-                            .get_local(
-                                &return_place
-                                    .local_or_deref_local()
-                                    .expect("deref return place failed"),
-                            )
-                            .expect("return local not found");
-                        let stack_top = function!(self); // needed in the closure
-                        let args = args
-                            .iter()
-                            .map(|operand| stack_top.op_to_local(operand))
-                            .collect();
-                        let return_place = function!(self)
-                            .get_basic_block_start(net, *return_block)
-                            .expect("cannot find return block");
-                        self.translate(function, args, data_return, start_place, return_place)
-                            .expect("translation error");
+                        let source_info = *function!(self).mir_body.source_info(location);
+                        self.unsupported("call of a non function type", source_info);
                     }
-                } else {
-                    function!(self)
-                        .handle_panic(net, self.unwind_abort_place)
-                        .expect("panic handling error");
                 }
             }
 
             Drop {
-                location: _,
+                location,
+                target,
+                unwind,
+            } => self.translate_drop(location, *target, *unwind)?,
+
+            // the new value is assigned after the old one was dropped
+            DropAndReplace {
+                location,
+                value,
                 target,
                 unwind,
-            } => function!(self)
-                .drop(net, *target, *unwind)
-                .expect("drop failed"),
+            } => {
+                self.translate_drop(location, *target, *unwind)?;
+                let function = function!(self);
+                let value = function.op_to_local(value)?;
+                let location = function.place_to_local(location)?;
+                self.propagate_links(value, location);
+            }
 
             Assert {
                 ref cond,
                 ref expected,
-                msg: _,
+                ref msg,
                 ref target,
                 ref cleanup,
-            } => function!(self)
-                .assert(net, cond, *expected, *target, *cleanup)
-                .expect("assert failed"),
+            } => {
+                let overflow = function!(self).is_overflow_check(cond);
+                let panic_place =
+                    if self.options.prune_panics && !(self.options.model_overflow && overflow) {
+                        None
+                    } else {
+                        Some(self.unwind_abort_place)
+                    };
+                function!(self).assert(
+                    net,
+                    cond,
+                    *expected,
+                    *target,
+                    *cleanup,
+                    panic_place,
+                    format!("assert_failed: {:?}", msg),
+                )?
+            }
+
+            Yield { resume, drop, .. } => function!(self).generator_yield(net, *resume, *drop)?,
+            GeneratorDrop => function!(self).generator_drop(net)?,
 
-            Yield { .. } => panic!("Yield"),
-            GeneratorDrop => panic!("GeneratorDrop"),
-            DropAndReplace { .. } => panic!("DropAndReplace"),
             Resume => {
-                function!(self)
-                    .resume(net, self.unwind_abort_place)
-                    .expect("resume failed");
-            }
-            Abort => function!(self)
-                .handle_panic(net, self.unwind_abort_place)
-                .expect("panic handling error"),
-            FalseEdges { .. } => bug!(
-                "should have been eliminated by\
-                 `simplify_branches` mir pass"
-            ),
-            FalseUnwind { .. } => bug!(
-                "should have been eliminated by\
-                 `simplify_branches` mir pass"
-            ),
-            Unreachable => debug!("unreachable"),
+                function!(self).resume(net)?;
+            }
+            Abort => function!(self).terminate(net, "Abort", self.aborted_place)?,
+            // only the real edges are executed (they are left in unoptimized MIR)
+            FalseEdges { real_target, .. } | FalseUnwind { real_target, .. } => {
+                function!(self).goto(net, *real_target)?
+            }
+            // the compiler asserts that the block is never reached, by default it stays a
+            // dead end, with a sink its reachability shows a soundness problem of the source
+            Unreachable => match self.unreachable_place {
+                Some(place) => function!(self).terminate(net, "Unreachable", place)?,
+                None => debug!("unreachable"),
+            },
+        }
+        Ok(())
+    }
+}
+
+impl<'tcx> Visitor<'tcx> for Translator<'tcx> {
+    fn visit_body(&mut self, body: ReadOnlyBodyAndCache<'_, 'tcx>) {
+        match body.phase {
+            MirPhase::Optimized => {}
+            _ => error!("tried to translate unoptimized MIR"),
+        }
+        self.super_body(body);
+    }
+
+    fn visit_basic_block_data(&mut self, block: BasicBlock, data: &BasicBlockData<'tcx>) {
+        trace!("---BasicBlock {:?}---", block);
+        // after an error the rest of the body is skipped
+        if self.error.is_some() {
+            return;
+        }
+        let result = self.activate_block(block);
+        self.record(result);
+        self.super_basic_block_data(block, data)
+    }
+
+    // The net transition of an assignment is built with the statement (see `add_statement`).
+    // Here only the mutex links are propagated to the assigned place:
+    // - Use, Repeat, Ref, Discriminant and AddressOf pass on the links of their source
+    // - a Cast passes them on if the result is still a pointer (e.g. `&T as *const T` or
+    //   unsizing `Box<T>` to `Box<dyn Trait>`), an integer or float cast is a new value
+    // - an aggregate (struct, tuple, enum, array or closure) passes on the links of its
+    //   operands (fields are not tracked on their own, so of every kind of resource
+    //   the last linked operand wins)
+    // - all other rvalues (arithmetic, Len, NullaryOp) are opaque and
+    //   produce new values without a link
+    fn visit_assign(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>, location: Location) {
+        let result = self.translate_assign(place, rvalue);
+        self.record(result);
+        self.super_assign(place, rvalue, location);
+    }
+
+    fn visit_source_info(&mut self, source_info: &SourceInfo) {
+        let result = self.set_source_info(source_info);
+        self.record(result);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement<'tcx>, location: Location) {
+        // no effect at runtime, a transition would only bloat the net
+        if let StatementKind::FakeRead(..) | StatementKind::Nop = statement.kind {
+            trace!("skipping {:?}", statement.kind);
+            return;
+        }
+        trace!("{:?}: ", statement.kind);
+        // the statement transition is added before its source info is visited
+        self.visit_source_info(&statement.source_info);
+        let result = self.translate_statement(statement);
+        self.record(result);
+        self.super_statement(statement, location);
+    }
+
+    fn visit_terminator_kind(&mut self, kind: &TerminatorKind<'tcx>, location: Location) {
+        trace!("{:?}", kind);
+
+        // check mutex links
+        if let TerminatorKind::Call {
+            ref args,
+            ref destination,
+            ..
+        } = kind
+        {
+            let result = self.link_call_arguments(args, destination);
+            self.record(result);
         }
+
+        let result = self.translate_terminator(kind, location);
+        self.record(result);
         self.super_terminator_kind(kind, location);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::CallStack;
+
+    #[test]
+    fn call_stack() {
+        let mut stack = CallStack::new();
+        assert!(stack.is_empty());
+        assert_eq!(stack.peek(), None);
+        stack.push(1);
+        stack.push(2);
+        assert!(!stack.is_empty());
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.peek(), Some(&2));
+        *stack.peek_mut().unwrap() = 3;
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+    }
+}
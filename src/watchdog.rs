@@ -0,0 +1,94 @@
+// Detects a translator that stopped making progress (e.g. a re-translation cycle)
+// and aborts with a diagnostic instead of hanging silently.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub struct Watchdog {
+    state: Arc<State>,
+}
+
+struct State {
+    // incremented every time a node is added to the net
+    progress: AtomicUsize,
+    // current length of the call stack
+    depth: AtomicUsize,
+    function: Mutex<String>,
+    finished: AtomicBool,
+}
+
+impl Watchdog {
+    /// Starts a thread that checks for progress once per `interval`.
+    /// If no progress was reported and the call stack did not shrink in that time
+    /// the current function is logged and the process exits.
+    pub fn new(interval: Duration) -> Self {
+        let state = Arc::new(State {
+            progress: AtomicUsize::new(0),
+            depth: AtomicUsize::new(0),
+            function: Mutex::new(String::new()),
+            finished: AtomicBool::new(false),
+        });
+        let observed = state.clone();
+        thread::spawn(move || {
+            let mut last_progress = observed.progress.load(Ordering::SeqCst);
+            let mut last_depth = observed.depth.load(Ordering::SeqCst);
+            loop {
+                thread::sleep(interval);
+                if observed.finished.load(Ordering::SeqCst) {
+                    break;
+                }
+                let progress = observed.progress.load(Ordering::SeqCst);
+                let depth = observed.depth.load(Ordering::SeqCst);
+                if progress == last_progress && depth >= last_depth {
+                    let function = observed.function.lock().expect("poisoned watchdog");
+                    error!(
+                        "translation made no progress for {:?} (call depth {}) in function: {}",
+                        interval, depth, function
+                    );
+                    std::process::exit(1);
+                }
+                last_progress = progress;
+                last_depth = depth;
+            }
+        });
+        Watchdog { state }
+    }
+
+    /// The handle through which the net reports every new node.
+    /// Visiting MIR is no progress on its own, a cycle that visits the same bodies
+    /// again and again without adding nodes is detected.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            state: self.state.clone(),
+        }
+    }
+
+    pub fn enter(&self, function: &str, depth: usize) {
+        *self.state.function.lock().expect("poisoned watchdog") = function.into();
+        self.state.depth.store(depth, Ordering::SeqCst);
+    }
+
+    pub fn leave(&self, depth: usize) {
+        self.state.depth.store(depth, Ordering::SeqCst);
+    }
+}
+
+/// Reports progress to a watchdog, it does not keep the watchdog running
+#[derive(Clone)]
+pub struct Progress {
+    state: Arc<State>,
+}
+
+impl Progress {
+    pub fn report(&self) {
+        self.state.progress.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.state.finished.store(true, Ordering::SeqCst);
+    }
+}
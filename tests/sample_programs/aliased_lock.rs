@@ -0,0 +1,11 @@
+use std::sync::Mutex;
+
+pub fn main() {
+    let first = Mutex::new(0);
+    let moved = first;
+    let alias = &moved;
+    let reborrow = &*alias;
+    let _outer = alias.lock().unwrap();
+    // blocks forever, both guards belong to the same mutex
+    let _inner = reborrow.lock().unwrap();
+}
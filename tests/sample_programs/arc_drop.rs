@@ -0,0 +1,12 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub fn main() {
+    let data = Arc::new(Mutex::new(0));
+    let shared = data.clone();
+    let handle = thread::spawn(move || {
+        *shared.lock().unwrap() += 1;
+    });
+    handle.join().unwrap();
+    drop(data);
+}
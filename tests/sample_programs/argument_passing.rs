@@ -0,0 +1,10 @@
+pub fn main() {
+    let a = 1;
+    let b = 2;
+    // `_1` and `_2` of `add` are the places of `a` and `b`
+    let c = add(a, b);
+}
+
+fn add(x: usize, y: usize) -> usize {
+    x + y
+}
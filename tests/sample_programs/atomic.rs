@@ -0,0 +1,20 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub fn main() {
+    let flag = Arc::new(AtomicUsize::new(0));
+    let thread_flag = flag.clone();
+    let handle = thread::spawn(move || {
+        thread_flag.store(1, Ordering::SeqCst);
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+    });
+    while flag
+        .compare_exchange(1, 2, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {}
+    COUNTER.load(Ordering::SeqCst);
+    handle.join().unwrap();
+}
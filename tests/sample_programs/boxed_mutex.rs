@@ -0,0 +1,10 @@
+use std::sync::Mutex;
+
+pub fn main() {
+    let boxed = Box::new(Mutex::new(0));
+    {
+        let mut guard = boxed.lock().unwrap();
+        *guard += 1;
+    }
+    let _again = boxed.lock().unwrap();
+}
@@ -0,0 +1,19 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub fn main() {
+    let first = Arc::new(Mutex::new(0));
+    let second = Arc::new(Mutex::new(0));
+    let thread_first = first.clone();
+    let thread_second = second.clone();
+    // locks in the opposite order of main, the net can deadlock
+    let worker = thread::spawn(move || {
+        let _second = thread_second.lock().unwrap();
+        let _first = thread_first.lock().unwrap();
+    });
+    {
+        let _first = first.lock().unwrap();
+        let _second = second.lock().unwrap();
+    }
+    worker.join().unwrap();
+}
@@ -0,0 +1,10 @@
+use std::sync::mpsc::channel;
+use std::thread;
+
+pub fn main() {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        sender.send(5).unwrap();
+    });
+    receiver.recv().unwrap();
+}
@@ -0,0 +1,20 @@
+use std::sync::mpsc::channel;
+use std::thread;
+
+pub fn main() {
+    let (sender, receiver) = channel();
+    let second = sender.clone();
+    let first_producer = thread::spawn(move || {
+        sender.send(1).unwrap();
+    });
+    let second_producer = thread::spawn(move || {
+        second.send(2).unwrap();
+    });
+    let consumer = thread::spawn(move || {
+        receiver.recv().unwrap();
+        receiver.recv().unwrap();
+    });
+    first_producer.join().unwrap();
+    second_producer.join().unwrap();
+    consumer.join().unwrap();
+}
@@ -0,0 +1,13 @@
+use std::sync::mpsc::channel;
+use std::thread;
+
+pub fn main() {
+    let (sender, receiver) = channel::<i32>();
+    let second = sender.clone();
+    thread::spawn(move || {
+        second.send(1).unwrap();
+    });
+    drop(sender);
+    // only fails after both senders are gone
+    while let Ok(_) = receiver.recv() {}
+}
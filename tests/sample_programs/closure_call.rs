@@ -0,0 +1,15 @@
+use std::sync::Mutex;
+
+pub fn main() {
+    let mutex = Mutex::new(0);
+    // the mutex is captured by reference
+    let increment = || {
+        let mut guard = mutex.lock().unwrap();
+        *guard += 1;
+    };
+    call(increment);
+}
+
+fn call<F: FnOnce()>(f: F) {
+    f()
+}
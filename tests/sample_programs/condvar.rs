@@ -0,0 +1,19 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+pub fn main() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let producer_pair = pair.clone();
+    let handle = thread::spawn(move || {
+        let (lock, condvar) = &*producer_pair;
+        *lock.lock().unwrap() = true;
+        condvar.notify_one();
+    });
+    let (lock, condvar) = &*pair;
+    let mut ready = lock.lock().unwrap();
+    while !*ready {
+        ready = condvar.wait(ready).unwrap();
+    }
+    drop(ready);
+    handle.join().unwrap();
+}
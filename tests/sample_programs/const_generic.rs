@@ -0,0 +1,15 @@
+#![feature(const_generics)]
+#![allow(incomplete_features)]
+
+fn pick<const N: usize>() -> usize {
+    match N {
+        0 => 10,
+        _ => 20,
+    }
+}
+
+pub fn main() {
+    // every instance knows its parameter, so each match keeps a single branch
+    let zero = pick::<0>();
+    let three = pick::<3>();
+}
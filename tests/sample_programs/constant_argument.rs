@@ -0,0 +1,7 @@
+pub fn main() {
+    helper(42);
+}
+
+fn helper(i: usize) -> usize {
+    i
+}
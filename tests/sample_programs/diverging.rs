@@ -0,0 +1,10 @@
+pub fn main() {
+    let x = 1;
+    if x > 0 {
+        stop(x);
+    }
+}
+
+fn stop(code: i32) -> ! {
+    std::process::exit(code)
+}
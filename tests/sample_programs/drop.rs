@@ -0,0 +1,11 @@
+struct Noisy(usize);
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        self.0 = 0;
+    }
+}
+
+pub fn main() {
+    let _noisy = Noisy(5);
+}
@@ -0,0 +1,11 @@
+// the scrutinee and the guard bindings are read by `FakeRead`s, they add no transitions
+
+pub fn main() {
+    let value = Some(3);
+    let x = match value {
+        Some(n) if n > 2 => n,
+        Some(n) => n + 1,
+        None => 0,
+    };
+    let _ = x;
+}
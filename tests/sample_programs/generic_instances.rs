@@ -0,0 +1,16 @@
+use std::sync::Mutex;
+
+pub fn main() {
+    let number = Mutex::new(0);
+    let text = Mutex::new("");
+    // two instances of `lock_twice` that lock different mutexes
+    lock_twice(&number);
+    lock_twice(&text);
+}
+
+fn lock_twice<T>(mutex: &Mutex<T>) {
+    {
+        let _guard = mutex.lock().unwrap();
+    }
+    let _guard = mutex.lock().unwrap();
+}
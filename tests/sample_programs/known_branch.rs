@@ -0,0 +1,9 @@
+pub fn main() {
+    let mut flag = true;
+    let x = if flag { 1 } else { 2 };
+    flag = false;
+    let y = match flag {
+        true => 3,
+        false => 4,
+    };
+}
@@ -0,0 +1,12 @@
+enum Signal {
+    Stop,
+    Go(u32),
+}
+
+pub fn main() {
+    let signal = Signal::Go(5);
+    let x = match signal {
+        Signal::Stop => 0,
+        Signal::Go(speed) => speed,
+    };
+}
@@ -0,0 +1,14 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub fn main() {
+    let data = Arc::new(Mutex::new(0));
+    let worker_data = data.clone();
+    let worker = thread::spawn(move || {
+        let _guard = worker_data.lock().unwrap();
+        panic!("worker failed");
+    });
+    let _ = worker.join();
+    // the mutex is poisoned if the worker panicked
+    let poisoned = data.lock().is_err();
+}
@@ -0,0 +1,9 @@
+use std::sync::{Arc, Mutex};
+
+pub fn main() {
+    let data = Arc::new(Mutex::new(0));
+    {
+        let _d1 = data.lock();
+    }
+    let _d2 = data.lock(); // d1 was dropped, so this does not deadlock
+}
@@ -0,0 +1,16 @@
+use std::sync::Once;
+use std::thread;
+
+static INIT: Once = Once::new();
+
+fn init() {
+    INIT.call_once(|| {
+        let setup = 1 + 1;
+    });
+}
+
+pub fn main() {
+    let worker = thread::spawn(|| init());
+    init();
+    worker.join().unwrap();
+}
@@ -0,0 +1,8 @@
+pub fn main() {
+    let y = increment(255);
+}
+
+fn increment(x: u8) -> u8 {
+    // CheckedAdd followed by an overflow assertion
+    x + 1
+}
@@ -0,0 +1,10 @@
+use std::sync::Mutex;
+
+pub fn main() {
+    let mutex = Mutex::new(0);
+    let pointer = &mutex as *const Mutex<i32>;
+    let alias = unsafe { &*pointer };
+    let _first = mutex.lock().unwrap();
+    // blocks forever, the cast pointer still points to the same mutex
+    let _second = alias.lock().unwrap();
+}
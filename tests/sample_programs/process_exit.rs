@@ -0,0 +1,10 @@
+fn check(value: usize) {
+    if value > 3 {
+        std::process::exit(1);
+    }
+}
+
+pub fn main() {
+    check(5);
+    std::process::abort();
+}
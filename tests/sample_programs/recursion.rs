@@ -0,0 +1,13 @@
+pub fn main() {
+    let x = count_down(3);
+    // the second call reuses the translation of the first one
+    let y = count_down(x);
+}
+
+fn count_down(i: usize) -> usize {
+    if i == 0 {
+        0
+    } else {
+        count_down(i - 1)
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+struct Shared {
+    data: Arc<Mutex<u32>>,
+}
+
+pub fn main() {
+    let data = Arc::new(Mutex::new(0));
+    let shared = Shared { data: data.clone() };
+    let handle = thread::spawn(move || {
+        let mut guard = shared.data.lock().unwrap();
+        *guard += 1;
+    });
+    {
+        let mut guard = data.lock().unwrap();
+        *guard += 1;
+    }
+    handle.join().unwrap();
+}
@@ -0,0 +1,5 @@
+pub fn main() {
+    let mut x = 5;
+    let y = &mut x;
+    *y += 1;
+}
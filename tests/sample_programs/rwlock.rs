@@ -0,0 +1,13 @@
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+pub fn main() {
+    let data = Arc::new(RwLock::new(0));
+    let writer_data = data.clone();
+    let handle = thread::spawn(move || {
+        *writer_data.write().unwrap() += 1;
+    });
+    let value = *data.read().unwrap();
+    handle.join().unwrap();
+    drop(value);
+}
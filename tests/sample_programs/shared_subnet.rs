@@ -0,0 +1,9 @@
+fn leaf(value: usize) -> usize {
+    value + 1
+}
+
+pub fn main() {
+    let first = leaf(1);
+    let second = leaf(first);
+    leaf(second);
+}
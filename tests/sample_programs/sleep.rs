@@ -0,0 +1,10 @@
+use std::thread;
+use std::time::Duration;
+
+pub fn main() {
+    let worker = thread::spawn(|| {
+        thread::yield_now();
+    });
+    thread::sleep(Duration::from_millis(10));
+    worker.join().unwrap();
+}
@@ -0,0 +1,11 @@
+use std::thread;
+
+pub fn main() {
+    let mut handles = Vec::new();
+    for _ in 0..3 {
+        handles.push(thread::spawn(|| {}));
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+struct SpinLock {
+    locked: AtomicBool,
+}
+
+impl SpinLock {
+    fn lock(&self) {
+        while self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {}
+    }
+
+    fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+pub fn main() {
+    let lock = Arc::new(SpinLock {
+        locked: AtomicBool::new(false),
+    });
+    let thread_lock = lock.clone();
+    let worker = thread::spawn(move || {
+        thread_lock.lock();
+        thread_lock.unlock();
+    });
+    lock.lock();
+    lock.unlock();
+    worker.join().unwrap();
+}
@@ -0,0 +1,11 @@
+static LIMIT: usize = 3;
+
+pub fn main() {
+    let x = LIMIT;
+    let y = limit();
+}
+
+fn limit() -> usize {
+    // the same place as in main
+    LIMIT
+}
@@ -0,0 +1,8 @@
+use std::thread;
+
+pub fn main() {
+    let handle = thread::spawn(|| {
+        let _x = 5;
+    });
+    handle.join().unwrap();
+}
@@ -0,0 +1,7 @@
+use std::thread;
+
+pub fn main() {
+    let _handle = thread::spawn(|| {
+        let _x = 5;
+    });
+}
@@ -0,0 +1,28 @@
+use std::sync::Mutex;
+
+trait Worker {
+    fn work(&self, data: &Mutex<u32>);
+
+    fn twice(&self, data: &Mutex<u32>) {
+        self.work(data);
+        self.work(data);
+    }
+}
+
+struct Incrementer;
+
+impl Worker for Incrementer {
+    fn work(&self, data: &Mutex<u32>) {
+        *data.lock().unwrap() += 1;
+    }
+}
+
+fn run<W: Worker>(worker: &W, data: &Mutex<u32>) {
+    worker.twice(data);
+    <W as Worker>::work(worker, data);
+}
+
+pub fn main() {
+    let data = Mutex::new(0);
+    run(&Incrementer, &data);
+}
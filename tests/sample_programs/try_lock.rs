@@ -0,0 +1,19 @@
+use std::sync::{Mutex, RwLock};
+
+pub fn main() {
+    let mutex = Mutex::new(0);
+    let guard = mutex.lock().unwrap();
+    // fails, the mutex is held
+    if let Ok(mut data) = mutex.try_lock() {
+        *data += 1;
+    }
+    drop(guard);
+
+    let lock = RwLock::new(0);
+    if let Ok(data) = lock.try_read() {
+        let _ = *data;
+    }
+    if let Ok(mut data) = lock.try_write() {
+        *data += 1;
+    }
+}
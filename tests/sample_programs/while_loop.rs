@@ -0,0 +1,7 @@
+pub fn main() {
+    let mut i = 0;
+    // the loop header is entered from the start and from the back edge
+    while i < 3 {
+        i += 1;
+    }
+}
@@ -1,11 +1,31 @@
 // build on https://rust-lang-nursery.github.io/cli-wg/tutorial/testing.html#testing-cli-applications-by-running-them
 use assert_cmd::prelude::*; // Add methods on commands
                             // use predicates::prelude::*;
-use std::process::Command; // Run programs // Used for writing assertions
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output}; // Run programs // Used for writing assertions
 
-fn test_program(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+// The file the net of the sample program is written to (without extension),
+// every set of arguments gets its own file because the tests run in parallel
+fn net_file(path: &str, args: &[&str]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let name = Path::new(path)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .ok_or("no program name")?;
+    let name: Vec<&str> = std::iter::once(name).chain(args.iter().copied()).collect();
+    Ok(std::env::temp_dir().join("granite").join(name.join(" ")))
+}
+
+// Translates the sample program with further arguments for granite and returns the
+// emitted net (in the JSON export) and the output of the translation
+fn translate(path: &str, args: &[&str]) -> Result<(Value, Output), Box<dyn std::error::Error>> {
+    let net_file = net_file(path, args)?.with_extension("json");
     let mut cmd = Command::main_binary()?;
     cmd.arg(path);
+    cmd.args(&["--", "--format", "json", "--force", "--output"]);
+    cmd.arg(&net_file);
+    cmd.args(args);
     cmd.env("RUST_BACKTRACE", "1");
     cmd.env("RUST_LOG", "trace");
     // has to point to the toolchain declared in ``rust-toolchain`` file
@@ -20,7 +40,68 @@ fn test_program(path: &str) -> Result<(), Box<dyn std::error::Error>> {
     if output.status.success() {
         println!("{}", String::from_utf8_lossy(&output.stdout));
     };
-    Ok(())
+    let net = serde_json::from_reader(std::fs::File::open(net_file)?)?;
+    Ok((net, output.clone()))
+}
+
+// Translates the sample program and returns the emitted net
+fn test_program(path: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    Ok(translate(path, &[])?.0)
+}
+
+// the size of the net that is reported with `--stats-json`
+fn net_stats(path: &str, args: &[&str]) -> Result<Value, Box<dyn std::error::Error>> {
+    // the JSON is printed even in quiet mode, without the log it is easier to find
+    let args: Vec<&str> = args
+        .iter()
+        .copied()
+        .chain(vec!["--stats-json", "--quiet"])
+        .collect();
+    let (_, output) = translate(path, &args)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stats = stderr
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .last()
+        .ok_or("no stats reported")?;
+    Ok(serde_json::from_str(stats)?)
+}
+
+// the ids of the `places` or `transitions` with a matching name
+fn node_ids<'net>(net: &'net Value, kind: &str, matches: impl Fn(&str) -> bool) -> Vec<&'net str> {
+    net[kind]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|node| node["name"].as_str().map_or(false, &matches))
+        .filter_map(|node| node["id"].as_str())
+        .collect()
+}
+
+fn has_arc(net: &Value, source: &str, target: &str) -> bool {
+    net["arcs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|arc| arc["source"] == source && arc["target"] == target)
+}
+
+// the unlocked places of the mutexes that are taken by a (healthy) lock
+fn locked_mutexes(net: &Value) -> HashSet<&str> {
+    let locks = node_ids(net, "transitions", |name| {
+        name.contains("std::sync::Mutex::<T>::lock") && !name.ends_with("poisoned")
+    });
+    assert_eq!(locks.len(), 2, "both locks are translated");
+    let unlocked = node_ids(net, "places", |name| name.ends_with(" unlocked"));
+    unlocked
+        .into_iter()
+        .filter(|place| locks.iter().any(|lock| has_arc(net, place, lock)))
+        .collect()
+}
+
+// the branches of a SwitchInt that are left after the pruning
+fn switch_branches(net: &Value) -> usize {
+    node_ids(net, "transitions", |name| name.starts_with("switch ")).len()
 }
 
 #[test]
@@ -42,3 +123,389 @@ fn minimal_deadlock_test() {
 fn function_call_test() {
     test_program("tests/sample_programs/function_call.rs").unwrap();
 }
+
+#[test]
+fn switch_int_test() {
+    test_program("tests/sample_programs/switch_int.rs").unwrap();
+}
+
+#[test]
+fn drop_test() {
+    test_program("tests/sample_programs/drop.rs").unwrap();
+}
+
+#[test]
+fn retag_test() {
+    test_program("tests/sample_programs/retag.rs").unwrap();
+}
+
+#[test]
+fn thread_spawn_test() {
+    test_program("tests/sample_programs/thread_spawn.rs").unwrap();
+}
+
+#[test]
+fn thread_join_test() {
+    test_program("tests/sample_programs/thread_join.rs").unwrap();
+}
+
+#[test]
+fn mutex_unlock_test() {
+    let net = test_program("tests/sample_programs/mutex_unlock.rs").unwrap();
+    // does not deadlock, dropping the first guard unlocks the mutex for the second lock
+    let unlocked = locked_mutexes(&net);
+    assert_eq!(unlocked.len(), 1);
+    let unlocked = unlocked.into_iter().next().unwrap();
+    let locked = node_ids(&net, "places", |name| name.ends_with(" locked"));
+    let unlocks = node_ids(&net, "transitions", |_| true)
+        .into_iter()
+        .filter(|t| has_arc(&net, t, unlocked) && locked.iter().any(|l| has_arc(&net, l, t)))
+        .count();
+    assert!(unlocks > 0, "the guard is dropped");
+}
+
+#[test]
+fn channel_test() {
+    test_program("tests/sample_programs/channel.rs").unwrap();
+}
+
+#[test]
+fn recursion_test() {
+    test_program("tests/sample_programs/recursion.rs").unwrap();
+}
+
+#[test]
+fn diverging_test() {
+    test_program("tests/sample_programs/diverging.rs").unwrap();
+}
+
+#[test]
+fn constant_argument_test() {
+    test_program("tests/sample_programs/constant_argument.rs").unwrap();
+}
+
+#[test]
+fn static_test() {
+    test_program("tests/sample_programs/static.rs").unwrap();
+}
+
+#[test]
+fn argument_passing_test() {
+    test_program("tests/sample_programs/argument_passing.rs").unwrap();
+}
+
+#[test]
+fn while_loop_test() {
+    test_program("tests/sample_programs/while_loop.rs").unwrap();
+}
+
+#[test]
+fn closure_call_test() {
+    test_program("tests/sample_programs/closure_call.rs").unwrap();
+}
+
+#[test]
+fn generic_instances_test() {
+    test_program("tests/sample_programs/generic_instances.rs").unwrap();
+}
+
+#[test]
+fn overflow_test() {
+    test_program("tests/sample_programs/overflow.rs").unwrap();
+}
+
+#[test]
+fn process_exit_test() {
+    test_program("tests/sample_programs/process_exit.rs").unwrap();
+}
+
+#[test]
+fn arc_drop_test() {
+    test_program("tests/sample_programs/arc_drop.rs").unwrap();
+}
+
+#[test]
+fn rwlock_test() {
+    test_program("tests/sample_programs/rwlock.rs").unwrap();
+}
+
+#[test]
+fn atomic_test() {
+    test_program("tests/sample_programs/atomic.rs").unwrap();
+}
+
+#[test]
+fn condvar_test() {
+    test_program("tests/sample_programs/condvar.rs").unwrap();
+}
+
+#[test]
+fn spawn_loop_test() {
+    test_program("tests/sample_programs/spawn_loop.rs").unwrap();
+}
+
+#[test]
+fn shared_subnet_test() {
+    let net = test_program("tests/sample_programs/shared_subnet.rs").unwrap();
+    // the second and third call pass their argument and return value to the subnet
+    // of the first call, only while their own call site is marked
+    let call_sites = node_ids(&net, "places", |name| {
+        name.contains("leaf") && name.ends_with(" call site")
+    });
+    assert_eq!(call_sites.len(), 3);
+    for kind in &["pass argument 1 to", "pass return value of"] {
+        let passes = node_ids(&net, "transitions", |name| {
+            name.starts_with(kind) && name.contains("leaf")
+        });
+        assert_eq!(passes.len(), 2, "{} for every later call", kind);
+        let accessed: Vec<HashSet<&str>> = passes
+            .iter()
+            .map(|t| {
+                net["arcs"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|arc| arc["target"] == *t)
+                    .filter_map(|arc| arc["source"].as_str())
+                    .filter(|place| has_arc(&net, t, place))
+                    .collect()
+            })
+            .collect();
+        for places in &accessed {
+            assert_eq!(
+                places.len(),
+                3,
+                "call site, local of the call and of the subnet"
+            );
+            assert_eq!(places.iter().filter(|p| call_sites.contains(p)).count(), 1);
+        }
+        // only the local of the subnet is the same for both calls
+        assert_eq!(accessed[0].intersection(&accessed[1]).count(), 1);
+    }
+}
+
+#[test]
+fn known_branch_test() {
+    let net = test_program("tests/sample_programs/known_branch.rs").unwrap();
+    // both conditions are known, each SwitchInt keeps a single branch
+    assert_eq!(switch_branches(&net), 2);
+}
+
+#[test]
+fn known_variant_test() {
+    let net = test_program("tests/sample_programs/known_variant.rs").unwrap();
+    // the variant is known, the `Stop` branch and `otherwise` have no transition
+    assert_eq!(switch_branches(&net), 1);
+}
+
+#[test]
+fn const_generic_test() {
+    let net = test_program("tests/sample_programs/const_generic.rs").unwrap();
+    // the parameter of each instance selects one branch
+    assert_eq!(switch_branches(&net), 2);
+}
+
+#[test]
+fn resource_struct_test() {
+    test_program("tests/sample_programs/resource_struct.rs").unwrap();
+}
+
+#[test]
+fn aliased_lock_test() {
+    let net = test_program("tests/sample_programs/aliased_lock.rs").unwrap();
+    // blocks forever, the second lock waits for the same mutex place
+    assert_eq!(locked_mutexes(&net).len(), 1);
+}
+
+#[test]
+fn try_lock_test() {
+    test_program("tests/sample_programs/try_lock.rs").unwrap();
+}
+
+#[test]
+fn mutex_poison_test() {
+    test_program("tests/sample_programs/mutex_poison.rs").unwrap();
+}
+
+#[test]
+fn once_test() {
+    let net = test_program("tests/sample_programs/once.rs").unwrap();
+    // the transitions of the resource model and of the calls are in the node map
+    let spans = |matches: &dyn Fn(&str) -> bool| -> Vec<bool> {
+        net["transitions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|t| t["name"].as_str().map_or(false, matches))
+            .map(|t| t["span"].is_string())
+            .collect()
+    };
+    let once = spans(&|name| name.starts_with("Once "));
+    assert!(!once.is_empty() && once.iter().all(|span| *span));
+    let calls = spans(&|name| name.starts_with("call ") || name.starts_with("return "));
+    assert!(!calls.is_empty() && calls.iter().any(|span| *span));
+}
+
+#[test]
+fn channel_clone_test() {
+    test_program("tests/sample_programs/channel_clone.rs").unwrap();
+}
+
+#[test]
+fn channel_disconnect_test() {
+    let net = test_program("tests/sample_programs/channel_disconnect.rs").unwrap();
+    // only the drop of the last sender disconnects the channel
+    let disconnected = node_ids(&net, "places", |name| name.ends_with(" disconnected"));
+    let last_drops = node_ids(&net, "transitions", |name| name == "drop last Sender");
+    assert!(!last_drops.is_empty());
+    for drop in last_drops {
+        assert!(disconnected.iter().any(|place| has_arc(&net, drop, place)));
+    }
+    let plain_drops = node_ids(&net, "transitions", |name| name == "drop");
+    assert!(plain_drops
+        .iter()
+        .all(|drop| disconnected.iter().all(|place| !has_arc(&net, drop, place))));
+}
+
+#[test]
+fn pointer_cast_test() {
+    let net = test_program("tests/sample_programs/pointer_cast.rs").unwrap();
+    // blocks forever, the lock through the pointer waits for the same mutex place
+    assert_eq!(locked_mutexes(&net).len(), 1);
+}
+
+#[test]
+fn sleep_test() {
+    test_program("tests/sample_programs/sleep.rs").unwrap();
+}
+
+#[test]
+fn boxed_mutex_test() {
+    test_program("tests/sample_programs/boxed_mutex.rs").unwrap();
+}
+
+#[test]
+fn spinlock_test() {
+    test_program("tests/sample_programs/spinlock.rs").unwrap();
+}
+
+#[test]
+fn trait_method_test() {
+    test_program("tests/sample_programs/trait_method.rs").unwrap();
+}
+
+#[test]
+fn captured_mutexes_test() {
+    test_program("tests/sample_programs/captured_mutexes.rs").unwrap();
+}
+
+#[test]
+fn fake_read_test() {
+    test_program("tests/sample_programs/fake_read.rs").unwrap();
+}
+
+#[test]
+fn net_size_test() {
+    let path = "tests/sample_programs/function_call.rs";
+    let size = |granularity| {
+        let stats = net_stats(path, &["--granularity", granularity]).unwrap();
+        (
+            stats["places"].as_u64().unwrap(),
+            stats["transitions"].as_u64().unwrap(),
+        )
+    };
+    let (statement_places, statement_transitions) = size("statement");
+    let (block_places, block_transitions) = size("block");
+    let (sync_places, sync_transitions) = size("sync");
+    // a coarser granularity never adds nodes
+    assert!(block_places <= statement_places && block_transitions <= statement_transitions);
+    assert!(sync_places <= block_places && sync_transitions <= block_transitions);
+
+    // an empty main is only its subnet and the places of the program
+    let stats = net_stats("tests/sample_programs/minimal_program.rs", &[]).unwrap();
+    assert!(stats["places"].as_u64().unwrap() <= 32);
+    assert!(stats["transitions"].as_u64().unwrap() <= 16);
+    let main = &stats["functions"]["minimal_program::main"];
+    assert!(main["transitions"].as_u64().unwrap() >= 1);
+    // every node of main is part of the total
+    assert!(main["places"].as_u64() <= stats["places"].as_u64());
+}
+
+#[test]
+fn dot_test() {
+    let path = "tests/sample_programs/function_call.rs";
+    let args = ["--format", "dot"];
+    translate(path, &args).unwrap();
+    let dot =
+        std::fs::read_to_string(net_file(path, &args).unwrap().with_extension("dot")).unwrap();
+    // one cluster per function, places as circles and transitions as boxes
+    assert!(dot.contains("label=\"function_call::main\";"));
+    assert!(dot.contains("label=\"function_call::call\";"));
+    assert!(dot.contains("shape=circle"));
+    assert!(dot.contains("shape=box"));
+    // the start place holds the initial token
+    assert!(dot.contains("label=\"function_call start\\n\u{2022}\""));
+}
+
+#[test]
+fn program_places_test() {
+    let net = test_program("tests/sample_programs/function_call.rs").unwrap();
+    // the export points to the start and end place, tools do not have to match their names
+    let start = node_ids(&net, "places", |name| name == "function_call start");
+    let end = node_ids(&net, "places", |name| name == "function_call end");
+    assert_eq!(net["start"].as_str(), start.first().copied());
+    assert_eq!(net["end"].as_str(), end.first().copied());
+    assert!(net["end"].is_string());
+}
+
+#[test]
+fn server_test() {
+    let mut cmd = Command::main_binary().unwrap();
+    cmd.arg("tests/sample_programs/minimal_deadlock.rs");
+    cmd.args(&["--", "--server", "--quiet"]);
+    cmd.env(
+        "LD_LIBRARY_PATH",
+        "/home/tom/.rustup/toolchains/nightly-2020-01-07-x86_64-unknown-linux-gnu/lib",
+    );
+    let result = cmd
+        .with_stdin()
+        .buffer("{\"function\": \"minimal_deadlock::main\"}\n")
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&result.get_output().stdout).to_string();
+    let response: Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+    assert_eq!(response["ok"], true);
+    assert_eq!(response["mutexes"], 1);
+    // the response carries the nodes of the net and the places of possible issues
+    let net = &response["net"];
+    assert!(!node_ids(net, "transitions", |_| true).is_empty());
+    assert!(net["arcs"]
+        .as_array()
+        .map_or(false, |arcs| !arcs.is_empty()));
+    let issues = response["issues"].as_array().unwrap();
+    let poisoned = node_ids(net, "places", |name| name == "Mutex_0 poisoned");
+    assert!(issues
+        .iter()
+        .any(|issue| issue["kind"] == "poison"
+            && issue["place"].as_str() == poisoned.first().copied()));
+    assert!(issues.iter().any(|issue| issue["kind"] == "deadlock"));
+}
+
+#[test]
+fn annotate_source_test() {
+    let args = ["--annotate-source"];
+    // the transitions of the resource models carry the location of their call
+    let annotated = |net: &Value, prefix: &str| {
+        let names = node_ids(net, "transitions", |name| name.starts_with(prefix));
+        let located = node_ids(net, "transitions", |name| {
+            name.starts_with(prefix) && name.contains(" @ ") && name.contains(".rs:")
+        });
+        !names.is_empty() && names.len() == located.len()
+    };
+    let (net, _) = translate("tests/sample_programs/condvar.rs", &args).unwrap();
+    assert!(annotated(&net, "Condvar::Wait"));
+    assert!(annotated(&net, "Condvar::wake"));
+    let (net, _) = translate("tests/sample_programs/once.rs", &args).unwrap();
+    assert!(annotated(&net, "Once init"));
+    assert!(annotated(&net, "Once skip"));
+}